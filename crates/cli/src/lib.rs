@@ -18,7 +18,17 @@ impl Cli {
         <Self as Parser>::parse()
     }
 
+    // scripts run on their own thread with a large stack, so deep recursion
+    // hits the interpreter's call depth limit instead of overflowing, even in
+    // unoptimised builds
     pub fn run(self) -> Result<(), Box<dyn std::error::Error>> {
-        self.command.run()
+        let thread = std::thread::Builder::new().stack_size(STACK_SIZE).spawn(move || self.command.run().map_err(|err| err.to_string()))?;
+
+        thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)).map_err(Into::into)
     }
 }
+
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+// how much of that scripts may use, leaving room for the cli's own frames
+pub(crate) const STACK_LIMIT: usize = STACK_SIZE - 8 * 1024 * 1024;
@@ -1,7 +1,8 @@
 pub mod repl;
 pub mod run;
 
-use clap::Subcommand;
+use clap::{Subcommand, ValueEnum};
+use coda_runtime::{error::CodaError, runtime::interpreter::Backend};
 
 #[derive(Subcommand)]
 pub enum Commands {
@@ -17,3 +18,34 @@ impl Commands {
         }
     }
 }
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum BackendArg {
+    tree,
+    vm,
+}
+
+impl From<BackendArg> for Backend {
+    fn from(arg: BackendArg) -> Self {
+        match arg {
+            BackendArg::tree => Backend::Tree,
+            BackendArg::vm => Backend::Vm,
+        }
+    }
+}
+
+// renders `err` against the source it points into. errors from imported
+// modules carry their own file, which is read back in for the snippet
+pub fn render_error(err: &CodaError, file: &str, source: &str) -> String {
+    match err.info().file.as_deref() {
+        Some(other) if other != file => err.render(&String::from_utf8_lossy(&std::fs::read(other).unwrap_or_default())),
+
+        _ => err.render(source),
+    }
+}
+
+// renders several errors at once, each prefixed the same way `main` prefixes
+// the first one
+pub fn render_errors(errors: &[CodaError], file: &str, source: &str) -> String {
+    errors.iter().map(|err| render_error(err, file, source)).collect::<Vec<_>>().join("\n\nerror: ")
+}
@@ -1,14 +1,24 @@
 use clap::Args;
 use coda_runtime::{
     frontend::{lexer, parser},
-    runtime::interpreter::Interpreter,
+    runtime::interpreter::{self, Interpreter},
     env::Env,
+    error::CodaError,
+    utils::StackGuard,
 };
+use crate::subcommands::{BackendArg, render_error, render_errors};
 use coda_std::std_loader;
 use rustyline::{Editor, error::ReadlineError, history::DefaultHistory};
 
 #[derive(Args)]
-pub struct Arguments {}
+pub struct Arguments {
+    #[arg(short, long, value_enum, default_value = "vm")]
+    pub backend: BackendArg,
+
+    /// extra roots for bare imports, searched before CODA_PATH
+    #[arg(long = "module-path", value_name = "DIR")]
+    pub module_path: Vec<std::path::PathBuf>,
+}
 
 impl Arguments {
     pub fn exec(self) -> Result<(), Box<dyn std::error::Error>> {
@@ -18,7 +28,11 @@ impl Arguments {
         let source_path = std::path::PathBuf::from(".");
         let base_path = source_path.parent().unwrap_or(std::path::Path::new(".")).to_path_buf();
         
-        let mut interpreter = Interpreter::new(env, base_path, Some(std_loader));
+        let mut interpreter = Interpreter::new(env, base_path, Some(Box::new(std_loader)));
+
+        interpreter.backend = self.backend.into();
+        interpreter.stack_limit = crate::STACK_LIMIT;
+        interpreter.module_paths = self.module_path.into_iter().chain(interpreter::coda_path()).collect();
 
         println!("coda repl (type ctrl+d to exit)");
 
@@ -36,16 +50,35 @@ impl Arguments {
                     
                     buffer.push_str(&line);
                     
-                    match lexer::scan(&buffer).and_then(|tokens| parser::parse(tokens)) {
-                        Ok(ast) => {
-                            interpreter.run(ast)?;
+                    let tokens = match lexer::scan(&buffer) {
+                        Ok(tokens) => tokens,
+                        Err(err) if is_unfinished(&err, &buffer) => continue,
+
+                        Err(err) => {
+                            eprintln!("error: {}", render_error(&err, "<repl>", &buffer));
+
                             rl.add_history_entry(buffer.trim())?;
                             buffer.clear();
-                        }
-                        Err(_) => {
+
                             continue;
                         }
+                    };
+
+                    let (ast, errors) = parser::parse_within(tokens, StackGuard::new(interpreter.stack_limit));
+
+                    // errors at the very end of the input just mean it isn't finished yet
+                    if !errors.is_empty() && errors.iter().all(|err| err.info().span.is_some_and(|span| span.start >= buffer.len())) {
+                        continue;
+                    }
+
+                    if !errors.is_empty() {
+                        eprintln!("error: {}", render_errors(&errors, "<repl>", &buffer));
+                    } else if let Err(err) = interpreter.run(ast) {
+                        eprintln!("error: {}", render_error(&err, "<repl>", &buffer));
                     }
+
+                    rl.add_history_entry(buffer.trim())?;
+                    buffer.clear();
                 }
 
                 Err(ReadlineError::Interrupted) => {
@@ -70,4 +103,12 @@ impl Arguments {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// a string, block comment or interpolation still open at the end of the input
+// just means there's more to come
+fn is_unfinished(err: &CodaError, buffer: &str) -> bool {
+    let info = err.info();
+
+    info.message.starts_with("unterminated") && info.span.is_some_and(|span| span.end >= buffer.len())
+}
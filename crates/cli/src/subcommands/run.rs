@@ -1,9 +1,11 @@
 use clap::Args;
 use coda_runtime::{
     frontend::{lexer, parser},
-    runtime::{interpreter::Interpreter},
+    runtime::interpreter::{self, Interpreter},
     env::Env,
+    utils::StackGuard,
 };
+use crate::subcommands::{BackendArg, render_error, render_errors};
 use coda_std::std_loader;
 use std::{error::Error, time::Instant};
 
@@ -11,6 +13,13 @@ use std::{error::Error, time::Instant};
 pub struct Arguments {
     #[arg(short, long)]
     pub file: String,
+
+    #[arg(short, long, value_enum, default_value = "vm")]
+    pub backend: BackendArg,
+
+    /// extra roots for bare imports, searched before CODA_PATH
+    #[arg(long = "module-path", value_name = "DIR")]
+    pub module_path: Vec<std::path::PathBuf>,
 }
 
 impl Arguments {
@@ -21,13 +30,29 @@ impl Arguments {
         let source_path = std::path::PathBuf::from(&self.file);
         let base_path = source_path.parent().unwrap_or(std::path::Path::new(".")).to_path_buf();
         
-        let mut interpreter = Interpreter::new(env, base_path, Some(std_loader));
+        let mut interpreter = Interpreter::new(env, base_path, Some(Box::new(std_loader)));
+
+        interpreter.backend = self.backend.into();
+        interpreter.stack_limit = crate::STACK_LIMIT;
+        interpreter.module_paths = self.module_path.into_iter().chain(interpreter::coda_path()).collect();
+        interpreter.file = Some(self.file.as_str().into());
+
+        // read as bytes so the lexer can point at invalid utf-8 instead of
+        // the whole read failing
+        let bytes = std::fs::read(&self.file)?;
+        let source = String::from_utf8_lossy(&bytes);
+        let tokens = lexer::scan_bytes(&bytes).map_err(|err| render_error(&err.in_file(self.file.as_str()), &self.file, &source))?;
+        let (ast, errors) = parser::parse_within(tokens, StackGuard::new(interpreter.stack_limit));
+
+        if !errors.is_empty() {
+            let errors: Vec<_> = errors.into_iter().map(|err| err.in_file(self.file.as_str())).collect();
 
-        let source = std::fs::read_to_string(&self.file)?;
-        let tokens = lexer::scan(&source)?;
-        let ast = parser::parse(tokens)?;
+            return Err(render_errors(&errors, &self.file, &source).into());
+        }
 
-        interpreter.run(ast)?;
+        if let Err(err) = interpreter.run(ast) {
+            return Err(render_error(&err.in_file(self.file.as_str()), &self.file, &source).into());
+        }
 
         println!("execution time: {:?}", start.elapsed());
 
@@ -1,10 +1,17 @@
-use crate::runtime::value::Value;
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use crate::{error::CodaError, runtime::value::Value};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    rc::Rc,
+};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Env {
     pub values: HashMap<String, Value>,
     pub exports: HashMap<String, Value>,
+    // names in `values` bound with `const`
+    pub constants: HashSet<String>,
     pub parent: Option<Rc<RefCell<Env>>>,
 }
 
@@ -14,6 +21,7 @@ impl Env {
         Self {
             values: HashMap::new(),
             exports: HashMap::new(),
+            constants: HashSet::new(),
             parent: None,
         }
     }
@@ -23,24 +31,35 @@ impl Env {
         Self {
             values: HashMap::new(),
             exports: HashMap::new(),
+            constants: HashSet::new(),
             parent,
         }
     }
 
-    pub fn assign(&mut self, name: &str, val: Value) -> Result<(), String> {
-        if self.values.contains_key(name) {
+    pub fn assign(&mut self, name: &str, val: Value) -> Result<(), CodaError> {
+        if self.constants.contains(name) {
+            Err(CodaError::runtime(format!("cannot assign to constant `{name}`")))
+        } else if self.values.contains_key(name) {
             self.values.insert(name.to_string(), val);
 
             Ok(())
         } else if let Some(parent) = &self.parent {
             parent.borrow_mut().assign(name, val)
         } else {
-            Err(format!("undefined variable `{name}`"))
+            Err(CodaError::runtime(format!("undefined variable `{name}`")))
         }
     }
 
+    // a new declaration replaces the old binding, constness included
     #[inline(always)]
     pub fn define(&mut self, name: String, val: Value) {
+        self.constants.remove(&name);
+        self.values.insert(name, val);
+    }
+
+    #[inline(always)]
+    pub fn define_const(&mut self, name: String, val: Value) {
+        self.constants.insert(name.clone());
         self.values.insert(name, val);
     }
 
@@ -55,10 +74,38 @@ impl Env {
         }
     }
     
+    #[inline(always)]
+    pub fn has(&self, name: &str) -> bool {
+        self.values.contains_key(name) || self.parent.as_ref().is_some_and(|parent| parent.borrow().has(name))
+    }
+
+    // what a `let`, `const` or `fn` declaration does to a module-level name
+    #[inline(always)]
+    pub fn declare(&mut self, name: String, value: Value, is_const: bool, is_exported: bool) {
+        match (is_const, is_exported) {
+            (true, true) => {
+                self.define_export(name.clone(), value);
+                self.constants.insert(name);
+            }
+            (true, false) => self.define_const(name, value),
+            (false, true) => self.define_export(name, value),
+            (false, false) => self.define(name, value),
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_const(&self, name: &str) -> bool {
+        if self.values.contains_key(name) {
+            self.constants.contains(name)
+        } else {
+            self.parent.as_ref().is_some_and(|parent| parent.borrow().is_const(name))
+        }
+    }
+
     #[inline(always)]
     pub fn define_export(&mut self, name: String, value: Value) {
         self.exports.insert(name.clone(), value.clone());
-        self.values.insert(name, value);
+        self.define(name, value);
     }
     
     #[inline(always)]
@@ -66,3 +113,49 @@ impl Env {
         self.exports.clone()
     }
 }
+
+// the locals of one function call, indexed by the slots the resolver assigned.
+// `parent` is the frame the function was defined in, so closures can reach
+// the locals they captured
+pub struct Frame {
+    pub slots: RefCell<Vec<Value>>,
+    pub parent: Option<Rc<Frame>>,
+}
+
+impl Frame {
+    #[inline(always)]
+    pub fn new(size: usize, parent: Option<Rc<Frame>>) -> Rc<Self> {
+        Rc::new(Self {
+            slots: RefCell::new(vec![Value::Null; size]),
+            parent,
+        })
+    }
+
+    #[inline(always)]
+    pub fn get(&self, depth: usize, index: usize) -> Value {
+        self.ancestor(depth).slots.borrow()[index].clone()
+    }
+
+    #[inline(always)]
+    pub fn set(&self, depth: usize, index: usize, value: Value) {
+        self.ancestor(depth).slots.borrow_mut()[index] = value;
+    }
+
+    #[inline(always)]
+    fn ancestor(&self, depth: usize) -> &Frame {
+        let mut frame = self;
+
+        for _ in 0..depth {
+            frame = frame.parent.as_deref().expect("slot depth escapes the outermost frame");
+        }
+
+        frame
+    }
+}
+
+// frames can hold closures that point back at them, so don't recurse into the slots
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Frame").field("slots", &self.slots.borrow().len()).finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,302 @@
+use crate::{
+    frontend::token::Span,
+    runtime::value::{Map, Value},
+};
+use std::{fmt, rc::Rc};
+
+// every error the runtime produces. the variant says which stage failed, so
+// embedders can match on it; the details are the same for all of them
+#[derive(Clone, Debug)]
+pub enum CodaError {
+    Lex(ErrorInfo),
+    Parse(ErrorInfo),
+    Resolve(ErrorInfo),
+    Runtime(ErrorInfo),
+    Import(ErrorInfo),
+    Native(ErrorInfo),
+    // a `throw` from a script
+    Thrown(Box<Thrown>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Thrown {
+    pub info: ErrorInfo,
+    pub value: Value,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorInfo {
+    pub message: String,
+    pub file: Option<Rc<str>>,
+    pub span: Option<Span>,
+    pub note: Option<Box<str>>,
+    // the calls the error came out of, outermost first. filled in by the
+    // interpreter the first time the error leaves a call
+    pub trace: Box<[TraceFrame]>,
+}
+
+// one call in a traceback. natives have no file or line
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceFrame {
+    pub function: String,
+    pub file: Option<Rc<str>>,
+    pub line: Option<usize>,
+}
+
+impl CodaError {
+    #[inline(always)]
+    pub fn lex(message: impl Into<String>, span: Span) -> Self {
+        CodaError::Lex(ErrorInfo::new(message).at(span))
+    }
+
+    #[inline(always)]
+    pub fn parse(message: impl Into<String>, span: Span) -> Self {
+        CodaError::Parse(ErrorInfo::new(message).at(span))
+    }
+
+    #[inline(always)]
+    pub fn resolve(message: impl Into<String>) -> Self {
+        CodaError::Resolve(ErrorInfo::new(message))
+    }
+
+    #[inline(always)]
+    pub fn runtime(message: impl Into<String>) -> Self {
+        CodaError::Runtime(ErrorInfo::new(message))
+    }
+
+    #[inline(always)]
+    pub fn import(message: impl Into<String>) -> Self {
+        CodaError::Import(ErrorInfo::new(message))
+    }
+
+    #[inline(always)]
+    pub fn native(message: impl Into<String>) -> Self {
+        CodaError::Native(ErrorInfo::new(message))
+    }
+
+    // the message is the value's `message` field if it's a map that has
+    // one, otherwise the value itself as `print` would show it
+    pub fn thrown(value: Value) -> Self {
+        // cloned out so the map isn't still borrowed when it's printed below
+        let field = match &value {
+            Value::Map(map) => map.borrow().get("message").cloned(),
+            _ => None,
+        };
+
+        let message = match field {
+            Some(Value::String(message)) => message,
+            _ => value.to_string(),
+        };
+
+        CodaError::Thrown(Box::new(Thrown { info: ErrorInfo::new(message), value }))
+    }
+
+    pub fn info(&self) -> &ErrorInfo {
+        match self {
+            CodaError::Lex(info) | CodaError::Parse(info) | CodaError::Resolve(info) | CodaError::Runtime(info) | CodaError::Import(info) | CodaError::Native(info) => info,
+            CodaError::Thrown(thrown) => &thrown.info,
+        }
+    }
+
+    pub fn info_mut(&mut self) -> &mut ErrorInfo {
+        match self {
+            CodaError::Lex(info) | CodaError::Parse(info) | CodaError::Resolve(info) | CodaError::Runtime(info) | CodaError::Import(info) | CodaError::Native(info) => info,
+            CodaError::Thrown(thrown) => &mut thrown.info,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CodaError::Lex(_) => "lex",
+            CodaError::Parse(_) => "parse",
+            CodaError::Resolve(_) => "resolve",
+            CodaError::Runtime(_) => "runtime",
+            CodaError::Import(_) => "import",
+            CodaError::Native(_) => "native",
+            CodaError::Thrown(_) => "error",
+        }
+    }
+
+    // what `catch` binds: a map with the error's `message`, `kind` and
+    // `trace`. a thrown map is copied, keeping its own `message` and `kind`
+    // if it has them, so the map the script threw is left untouched and
+    // `trace` is always where this throw came from
+    pub fn to_value(&self) -> Value {
+        let info = self.info();
+        let trace = info
+            .trace
+            .iter()
+            .map(|frame| {
+                let mut map = Map::default();
+
+                map.insert("function".to_string(), Value::String(frame.function.clone()));
+                map.insert("file".to_string(), frame.file.as_deref().map_or(Value::Null, |file| Value::String(file.to_string())));
+                map.insert("line".to_string(), frame.line.map_or(Value::Null, |line| Value::Int(line as i64)));
+
+                Value::map(map)
+            })
+            .collect();
+
+        let fields = [
+            ("message", Value::String(info.message.clone())),
+            ("kind", Value::String(self.kind().to_string())),
+            ("trace", Value::array(trace)),
+        ];
+
+        let mut map = match self {
+            CodaError::Thrown(thrown) => match &thrown.value {
+                Value::Map(map) => map.borrow().clone(),
+                _ => Map::default(),
+            },
+            _ => Map::default(),
+        };
+
+        for (key, value) in fields {
+            if key == "trace" || map.get(key).is_none() {
+                map.insert(key.to_string(), value);
+            }
+        }
+
+        Value::map(map)
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.info_mut().note = Some(note.into().into_boxed_str());
+        self
+    }
+
+    // like `in_file`, the innermost span an error passes through wins
+    pub fn at(mut self, span: Span) -> Self {
+        let info = self.info_mut();
+
+        if info.span.is_none() {
+            info.span = Some(span);
+        }
+
+        self
+    }
+
+    // only fills the file in if nothing closer to the error has already
+    pub fn in_file(mut self, file: impl Into<Rc<str>>) -> Self {
+        let info = self.info_mut();
+
+        if info.file.is_none() {
+            info.file = Some(file.into());
+        }
+
+        self
+    }
+
+    // renders the error with the offending source line and the span
+    // underlined. `source` should be the text of `info().file`
+    pub fn render(&self, source: &str) -> String {
+        let info = self.info();
+        let mut out = info.message.clone();
+
+        if let Some(Span { start, end, line, column }) = info.span {
+            let file = info.file.as_deref().unwrap_or("<input>");
+            let gutter = line.to_string().len();
+
+            out.push_str(&format!("\n{:gutter$}--> {file}:{line}:{column}", ""));
+
+            if let Some(text) = source.lines().nth(line.saturating_sub(1)) {
+                let pad: String = text.chars().take(column.saturating_sub(1)).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+                // only the first line of a multi-line span is shown
+                let width = source.get(start..end).and_then(|s| s.lines().next()).map_or(1, |s| s.chars().count().max(1));
+
+                out.push_str(&format!("\n{:gutter$} |", ""));
+                out.push_str(&format!("\n{line} | {text}"));
+                out.push_str(&format!("\n{:gutter$} | {pad}{}", "", "^".repeat(width)));
+            }
+        } else if let Some(file) = &info.file {
+            out.push_str(&format!("\n --> {file}"));
+        }
+
+        if let Some(note) = &info.note {
+            out.push_str(&format!("\n = note: {note}"));
+        }
+
+        if let Some(traceback) = self.traceback() {
+            out.push_str(&format!("\n\n{traceback}"));
+        }
+
+        out
+    }
+
+    // the calls the error passed through, one per line. `None` when it
+    // didn't come from inside a function
+    pub fn traceback(&self) -> Option<String> {
+        let trace = &self.info().trace;
+
+        if trace.len() < 2 {
+            return None;
+        }
+
+        let mut out = String::from("traceback (most recent call last):");
+        let mut index = 0;
+
+        // runs of the same frame, as deep recursion leaves, are shown once
+        while index < trace.len() {
+            let frame = &trace[index];
+            let repeats = trace[index + 1..].iter().take_while(|other| *other == frame).count();
+
+            out.push_str(&format!("\n  {frame}"));
+
+            if repeats > 0 {
+                out.push_str(&format!("\n  [previous frame repeated {repeats} more time{}]", if repeats == 1 { "" } else { "s" }));
+            }
+
+            index += 1 + repeats;
+        }
+
+        Some(out)
+    }
+}
+
+impl ErrorInfo {
+    #[inline(always)]
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+
+    #[inline(always)]
+    pub fn at(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl fmt::Display for CodaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = self.info();
+
+        match (&info.file, info.span) {
+            (Some(file), Some(Span { line, column, .. })) => write!(f, "{file}:{line}:{column}: ")?,
+            (None, Some(Span { line, column, .. })) => write!(f, "{line}:{column}: ")?,
+            (Some(file), None) => write!(f, "{file}: ")?,
+            (None, None) => {}
+        }
+
+        write!(f, "{}", info.message)?;
+
+        if let Some(note) = &info.note {
+            write!(f, " ({note})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for CodaError {}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (file, Some(line)) => write!(f, "{} ({}:{line})", self.function, file.as_deref().unwrap_or("<input>")),
+            (_, None) => write!(f, "{} (native)", self.function),
+        }
+    }
+}
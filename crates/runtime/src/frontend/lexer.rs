@@ -1,64 +1,161 @@
 use crate::{
-    frontend::token::{Token, TokenKind},
-    utils::{is_alpha, is_alphanumeric, slice_to_string},
+    error::CodaError,
+    frontend::token::{Span, Token, TokenKind},
+    utils::{is_ident_continue, is_ident_start},
 };
 
-pub fn scan(source: &str) -> Result<Vec<Token>, String> {
+pub fn scan(source: &str) -> Result<Vec<Token>, CodaError> {
     let mut lexer = Lexer::new(source);
     lexer.scan_tokens()
 }
 
+// for source that hasn't been checked to be utf-8 yet, e.g. straight from a
+// file. invalid utf-8 is reported where it starts
+pub fn scan_bytes(source: &[u8]) -> Result<Vec<Token>, CodaError> {
+    match std::str::from_utf8(source) {
+        Ok(source) => scan(source),
+
+        Err(err) => {
+            let valid = err.valid_up_to();
+            // everything before `valid` is known good, so this can't fail
+            let before = std::str::from_utf8(&source[..valid]).unwrap_or_default();
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+            let span = Span {
+                start: valid,
+                end: valid + err.error_len().unwrap_or(source.len() - valid),
+                line: before.matches('\n').count() + 1,
+                column: before[line_start..].chars().count() + 1,
+            };
+
+            Err(CodaError::lex(format!("invalid utf-8 at byte {valid}"), span))
+        }
+    }
+}
+
 struct Lexer<'a> {
-    src: &'a [u8],
+    src: &'a str,
     start: usize,
     current: usize,
     line: usize,
+    // column of `current`, counted in chars so it lines up with what's on screen
+    column: usize,
+    // line and column where the token being scanned starts
+    start_line: usize,
+    start_column: usize,
+    // one entry per `${` we're inside, counting the `{` opened since so we
+    // know which `}` ends the interpolation
+    interpolations: Vec<usize>,
+    // `///` lines seen since the last token, waiting to be attached to the
+    // declaration that follows them
+    doc: Option<String>,
 }
 
 impl<'a> Lexer<'a> {
     #[inline(always)]
     fn new(source: &'a str) -> Self {
         Self {
-            src: source.as_bytes(),
+            src: source,
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            interpolations: Vec::new(),
+            doc: None,
         }
     }
 
     #[inline(always)]
-    fn scan_tokens(&mut self) -> Result<Vec<Token>, String> {
+    fn scan_tokens(&mut self) -> Result<Vec<Token>, CodaError> {
         let mut tokens = Vec::new();
 
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column();
+
+            if let Some(mut token) = self.scan_token()? {
+                // doc comments only stick to declarations, anything else drops them
+                let doc = self.doc.take();
+
+                if matches!(token.kind, TokenKind::Let | TokenKind::Const | TokenKind::Fn | TokenKind::Export) {
+                    token.doc = doc;
+                }
 
-            if let Some(token) = self.scan_token()? {
                 tokens.push(token);
             }
         }
 
+        if !self.interpolations.is_empty() {
+            return Err(self.error("unterminated string interpolation"));
+        }
+
         tokens.push(Token {
             kind: TokenKind::EOF,
-            line: self.line,
+            doc: None,
+            span: Span {
+                start: self.current,
+                end: self.current,
+                line: self.line,
+                column: self.column(),
+            },
         });
 
         Ok(tokens)
     }
 
-    fn scan_token(&mut self) -> Result<Option<Token>, String> {
+    fn scan_token(&mut self) -> Result<Option<Token>, CodaError> {
         let c = self.advance();
 
         match c {
-            b'(' => Ok(Some(self.token(TokenKind::LParen))),
-            b')' => Ok(Some(self.token(TokenKind::RParen))),
-            b'{' => Ok(Some(self.token(TokenKind::LBrace))),
-            b'}' => Ok(Some(self.token(TokenKind::RBrace))),
-            b',' => Ok(Some(self.token(TokenKind::Comma))),
-            b'.' => Ok(Some(self.token(TokenKind::Dot))),
+            '(' => Ok(Some(self.token(TokenKind::LParen))),
+            ')' => Ok(Some(self.token(TokenKind::RParen))),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+
+                Ok(Some(self.token(TokenKind::LBrace)))
+            }
+
+            '}' => match self.interpolations.last_mut() {
+                // closes a `${`, so the string picks up again
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string(true)
+                }
+
+                Some(depth) => {
+                    *depth -= 1;
+
+                    Ok(Some(self.token(TokenKind::RBrace)))
+                }
 
-            b'+' => {
-                if self.peek() == b'=' {
+                None => Ok(Some(self.token(TokenKind::RBrace))),
+            },
+            ',' => Ok(Some(self.token(TokenKind::Comma))),
+            ';' => Ok(Some(self.token(TokenKind::Semicolon))),
+            ':' => Ok(Some(self.token(TokenKind::Colon))),
+
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == '.' {
+                    self.advance();
+                    self.advance();
+
+                    Ok(Some(self.token(TokenKind::DotDotDot)))
+                } else if self.peek() == '.' {
+                    self.advance();
+
+                    Ok(Some(self.token(TokenKind::DotDot)))
+                } else {
+                    Ok(Some(self.token(TokenKind::Dot)))
+                }
+            }
+
+            '+' => {
+                if self.peek() == '=' {
                     self.advance();
 
                     Ok(Some(self.token(TokenKind::PlusEqual)))
@@ -67,8 +164,8 @@ impl<'a> Lexer<'a> {
                 }
             }
 
-            b'-' => {
-                if self.peek() == b'=' {
+            '-' => {
+                if self.peek() == '=' {
                     self.advance();
 
                     Ok(Some(self.token(TokenKind::MinusEqual)))
@@ -77,25 +174,31 @@ impl<'a> Lexer<'a> {
                 }
             }
 
-            b'*' => {
-                if self.peek() == b'=' {
+            '*' => {
+                if self.peek() == '=' {
                     self.advance();
 
                     Ok(Some(self.token(TokenKind::StarEqual)))
+                } else if self.peek() == '*' {
+                    self.advance();
+
+                    Ok(Some(self.token(TokenKind::StarStar)))
                 } else {
                     Ok(Some(self.token(TokenKind::Star)))
                 }
             }
 
-            b'/' => {
-                if self.peek() == b'=' {
+            '/' => {
+                if self.peek() == '=' {
                     self.advance();
 
                     Ok(Some(self.token(TokenKind::SlashEqual)))
-                } else if self.peek() == b'/' {
-                    while self.peek() != b'\n' && !self.is_at_end() {
-                        self.advance();
-                    }
+                } else if self.peek() == '/' {
+                    self.line_comment();
+
+                    Ok(None)
+                } else if self.peek() == '*' {
+                    self.block_comment()?;
 
                     Ok(None)
                 } else {
@@ -103,8 +206,32 @@ impl<'a> Lexer<'a> {
                 }
             }
 
-            b'=' => {
-                if self.peek() == b'=' {
+            '%' => Ok(Some(self.token(TokenKind::Percent))),
+
+            '!' => {
+                if self.peek() == '=' {
+                    self.advance();
+
+                    Ok(Some(self.token(TokenKind::BangEqual)))
+                } else {
+                    Ok(Some(self.token(TokenKind::Bang)))
+                }
+            }
+
+            '&' if self.peek() == '&' => {
+                self.advance();
+
+                Ok(Some(self.token(TokenKind::And)))
+            }
+
+            '|' if self.peek() == '|' => {
+                self.advance();
+
+                Ok(Some(self.token(TokenKind::Or)))
+            }
+
+            '=' => {
+                if self.peek() == '=' {
                     self.advance();
 
                     Ok(Some(self.token(TokenKind::EqualEqual)))
@@ -113,18 +240,18 @@ impl<'a> Lexer<'a> {
                 }
             }
 
-            b' ' | b'\t' | b'\r' => Ok(None),
+            ' ' | '\t' | '\r' => Ok(None),
 
-            b'\n' => {
-                self.line += 1;
+            '\n' => {
+                self.newline();
 
                 Ok(None)
             }
 
-            b'"' => self.string(),
+            '"' => self.string(false),
 
-            b'>' => {
-                if self.peek() == b'=' {
+            '>' => {
+                if self.peek() == '=' {
                     self.advance();
 
                     Ok(Some(self.token(TokenKind::GreaterEqual)))
@@ -133,8 +260,8 @@ impl<'a> Lexer<'a> {
                 }
             }
 
-            b'<' => {
-                if self.peek() == b'=' {
+            '<' => {
+                if self.peek() == '=' {
                     self.advance();
 
                     Ok(Some(self.token(TokenKind::LessEqual)))
@@ -143,76 +270,344 @@ impl<'a> Lexer<'a> {
                 }
             }
 
-            b'[' => Ok(Some(self.token(TokenKind::LBracket))),
-            b']' => Ok(Some(self.token(TokenKind::RBracket))),
+            '[' => Ok(Some(self.token(TokenKind::LBracket))),
+            ']' => Ok(Some(self.token(TokenKind::RBracket))),
+
+            'r' if self.peek() == '"' || self.peek() == '#' => self.raw_string(),
 
             c if c.is_ascii_digit() => self.number(),
-            c if is_alpha(c) => self.identifier(),
+            c if is_ident_start(c) => self.identifier(),
 
-            _ => Err(format!("unexpected character at line {}", self.line)),
+            _ => Err(self.error(format!("unexpected character `{}`", c.escape_debug()))),
         }
     }
 
-    fn string(&mut self) -> Result<Option<Token>, String> {
-        while !self.is_at_end() && self.peek() != b'"' {
-            if self.peek() == b'\n' {
-                self.line += 1;
+    // scans up to the closing quote, or up to a `${`, in which case the text so
+    // far becomes a `StringPart` and the lexer carries on with the expression.
+    // `continued` is set when picking up after an interpolation's `}`
+    fn string(&mut self, continued: bool) -> Result<Option<Token>, CodaError> {
+        let mut value = String::new();
+
+        loop {
+            if self.is_at_end() {
+                return Err(self.error("unterminated string"));
             }
 
-            self.advance();
+            match self.advance() {
+                '"' => {
+                    let kind = if continued { TokenKind::StringEnd(value) } else { TokenKind::String(value) };
+
+                    return Ok(Some(self.token(kind)));
+                }
+
+                '$' if self.peek() == '{' => {
+                    self.advance();
+                    self.interpolations.push(0);
+
+                    return Ok(Some(self.token(TokenKind::StringPart(value))));
+                }
+
+                '\\' => {
+                    let c = self.escape()?;
+
+                    value.push(c);
+                }
+
+                '\n' => {
+                    self.newline();
+                    value.push('\n');
+                }
+
+                c => value.push(c),
+            }
         }
+    }
+
+    // the backslash has already been consumed
+    fn escape(&mut self) -> Result<char, CodaError> {
+        let start = self.current - 1;
 
         if self.is_at_end() {
-            return Err("unterminated string".into());
+            return Err(self.error("unterminated string"));
+        }
+
+        Ok(match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            '$' => '$',
+
+            // `\u{1F600}`: one to six hex digits
+            'u' => {
+                if self.peek() != '{' {
+                    return Err(self.error_from(start, "expected '{' after `\\u`"));
+                }
+
+                self.advance();
+
+                let digits = self.current;
+
+                while self.peek().is_ascii_hexdigit() {
+                    self.advance();
+                }
+
+                let hex = &self.src[digits..self.current];
+
+                if self.peek() != '}' || hex.is_empty() || hex.len() > 6 {
+                    return Err(self.error_from(start, "malformed unicode escape"));
+                }
+
+                self.advance();
+
+                u32::from_str_radix(hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error_from(start, format!("`\\u{{{hex}}}` is not a valid unicode character")))?
+            }
+
+            c => return Err(self.error_from(start, format!("unknown escape sequence `\\{}`", c.escape_debug()))),
+        })
+    }
+
+    // `r"..."` or `r#"..."#`, with as many `#`s as needed to allow quotes
+    // inside. no escapes and no interpolation
+    fn raw_string(&mut self) -> Result<Option<Token>, CodaError> {
+        let mut hashes = 0;
+
+        while self.peek() == '#' {
+            self.advance();
+            hashes += 1;
+        }
+
+        if self.peek() != '"' {
+            return Err(self.error("expected '\"' to start raw string"));
         }
 
         self.advance();
 
-        let value = slice_to_string(&self.src[self.start + 1..self.current - 1]);
+        let content = self.current;
+
+        loop {
+            if self.is_at_end() {
+                return Err(self.error("unterminated raw string"));
+            }
+
+            match self.advance() {
+                '"' if self.src[self.current..].bytes().take(hashes).filter(|&c| c == b'#').count() == hashes => break,
+
+                '\n' => self.newline(),
+
+                _ => {}
+            }
+        }
+
+        let value = self.src[content..self.current - 1].to_string();
+
+        self.current += hashes;
+        self.column += hashes;
 
         Ok(Some(self.token(TokenKind::String(value))))
     }
 
-    fn number(&mut self) -> Result<Option<Token>, String> {
-        while self.peek().is_ascii_digit() {
+    // the first digit has already been consumed
+    fn number(&mut self) -> Result<Option<Token>, CodaError> {
+        if self.src.as_bytes()[self.start] == b'0' && matches!(self.peek(), 'x' | 'X' | 'b' | 'B' | 'o' | 'O') {
+            let (radix, kind) = match self.advance().to_ascii_lowercase() {
+                'x' => (16, "hex"),
+                'b' => (2, "binary"),
+                _ => (8, "octal"),
+            };
+
+            let mut digits = String::new();
+
+            self.digits(radix, &mut digits)?;
+
+            if digits.is_empty() {
+                return Err(self.error(format!("expected digits in {kind} literal")));
+            }
+
+            self.check_literal_end(kind)?;
+
+            let value = i64::from_str_radix(&digits, radix).map_err(|_| self.error(format!("{kind} literal is too large")))?;
+
+            return Ok(Some(self.token(TokenKind::Int(value))));
+        }
+
+        let mut text = self.src[self.start..self.current].to_string();
+        let mut is_float = false;
+
+        self.digits(10, &mut text)?;
+
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+            is_float = true;
+
+            text.push(self.advance());
+
+            self.digits(10, &mut text)?;
+        }
+
+        if matches!(self.peek(), 'e' | 'E') {
+            is_float = true;
+            text.push(self.advance());
+
+            if matches!(self.peek(), '+' | '-') {
+                text.push(self.advance());
+            }
+
+            if !self.peek().is_ascii_digit() {
+                return Err(self.error("expected digits in exponent"));
+            }
+
+            self.digits(10, &mut text)?;
+        }
+
+        self.check_literal_end("number")?;
+
+        // no fraction or exponent makes it an int
+        if !is_float {
+            let value = text.parse::<i64>().map_err(|_| self.error("integer literal is too large"))?;
+
+            return Ok(Some(self.token(TokenKind::Int(value))));
+        }
+
+        let value = text.parse::<f64>().map_err(|_| self.error("malformed number literal"))?;
+
+        Ok(Some(self.token(TokenKind::Number(value))))
+    }
+
+    // appends digits of `radix` to `out`, skipping `_` separators, which are
+    // only allowed between two digits
+    fn digits(&mut self, radix: u32, out: &mut String) -> Result<(), CodaError> {
+        let mut after_digit = out.chars().last().is_some_and(|c| c.is_digit(radix));
+
+        loop {
+            let c = self.peek();
+
+            if c.is_digit(radix) {
+                out.push(c);
+                self.advance();
+
+                after_digit = true;
+            } else if c == '_' {
+                self.advance();
+
+                if !after_digit || !(self.peek()).is_digit(radix) {
+                    return Err(self.error("`_` in a number literal must be between digits"));
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    // a literal running straight into letters or digits, like `0b102` or
+    // `12abc`, is an error rather than two tokens
+    fn check_literal_end(&mut self, kind: &str) -> Result<(), CodaError> {
+        if !is_ident_continue(self.peek()) {
+            return Ok(());
+        }
+
+        let c = self.advance();
+
+        while is_ident_continue(self.peek()) {
             self.advance();
         }
 
-        if self.peek() == b'.' && self.peek_next().is_ascii_digit() {
+        Err(self.error(format!("invalid digit `{c}` in {kind} literal")))
+    }
+
+    // `// ...`, or a `/// ...` doc comment. `////` and longer are plain comments
+    fn line_comment(&mut self) {
+        self.advance();
+
+        let is_doc = self.peek() == '/' && self.peek_next() != '/';
+
+        while self.peek() != '\n' && !self.is_at_end() {
             self.advance();
+        }
 
-            while self.peek().is_ascii_digit() {
-                self.advance();
+        if !is_doc {
+            return;
+        }
+
+        let text = &self.src[self.start + 3..self.current];
+        let text = text.strip_prefix(' ').unwrap_or(text).trim_end();
+
+        match &mut self.doc {
+            Some(doc) => {
+                doc.push('\n');
+                doc.push_str(text);
             }
+
+            None => self.doc = Some(text.to_string()),
         }
+    }
 
-        let value = std::str::from_utf8(&self.src[self.start..self.current]).unwrap().parse::<f64>().unwrap();
+    // `/* ... */`, which can nest
+    fn block_comment(&mut self) -> Result<(), CodaError> {
+        self.advance();
 
-        Ok(Some(self.token(TokenKind::Number(value))))
+        let mut depth = 1usize;
+
+        while depth > 0 {
+            if self.is_at_end() {
+                return Err(self.error("unterminated block comment"));
+            }
+
+            match self.advance() {
+                '/' if self.peek() == '*' => {
+                    self.advance();
+                    depth += 1;
+                }
+
+                '*' if self.peek() == '/' => {
+                    self.advance();
+                    depth -= 1;
+                }
+
+                '\n' => self.newline(),
+
+                _ => {}
+            }
+        }
+
+        Ok(())
     }
 
-    fn identifier(&mut self) -> Result<Option<Token>, String> {
-        while is_alphanumeric(self.peek()) {
+    fn identifier(&mut self) -> Result<Option<Token>, CodaError> {
+        while is_ident_continue(self.peek()) {
             self.advance();
         }
 
-        let text = slice_to_string(&self.src[self.start..self.current]);
+        let text = &self.src[self.start..self.current];
 
-        let kind = match text.as_str() {
+        let kind = match text {
             "let" => TokenKind::Let,
             "const" => TokenKind::Const,
             "fn" => TokenKind::Fn,
             "if" => TokenKind::If,
             "else" => TokenKind::Else,
             "while" => TokenKind::While,
+            "for" => TokenKind::For,
+            "in" => TokenKind::In,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
             "return" => TokenKind::Return,
+            "try" => TokenKind::Try,
+            "catch" => TokenKind::Catch,
+            "finally" => TokenKind::Finally,
+            "throw" => TokenKind::Throw,
             "true" => TokenKind::True,
             "false" => TokenKind::False,
             "null" => TokenKind::Null,
             "import" => TokenKind::Import,
             "export" => TokenKind::Export,
 
-            _ => TokenKind::Identifier(text),
+            _ => TokenKind::Identifier(text.to_string()),
         };
 
         Ok(Some(self.token(kind)))
@@ -220,26 +615,67 @@ impl<'a> Lexer<'a> {
 
     #[inline(always)]
     fn token(&self, kind: TokenKind) -> Token {
-        Token { kind, line: self.line }
+        Token { kind, span: self.span(), doc: None }
+    }
+
+    // errors cover the token being scanned, up to where scanning stopped
+    #[inline(always)]
+    fn error(&self, message: impl Into<String>) -> CodaError {
+        CodaError::lex(message, self.span())
+    }
+
+    // for errors in the middle of a token, from `start` on the current line
+    #[inline(always)]
+    fn error_from(&self, start: usize, message: impl Into<String>) -> CodaError {
+        let span = Span {
+            start,
+            end: self.current,
+            line: self.line,
+            column: self.column - self.src[start..self.current].chars().count(),
+        };
+
+        CodaError::lex(message, span)
+    }
+
+    #[inline(always)]
+    fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.current,
+            line: self.start_line,
+            column: self.start_column,
+        }
+    }
+
+    #[inline(always)]
+    fn newline(&mut self) {
+        self.line += 1;
+        self.column = 1;
+    }
+
+    #[inline(always)]
+    fn column(&self) -> usize {
+        self.column
     }
 
     #[inline(always)]
-    fn advance(&mut self) -> u8 {
-        let c = self.src[self.current];
+    fn advance(&mut self) -> char {
+        let c = self.peek();
 
-        self.current += 1;
+        self.current += c.len_utf8();
+        self.column += 1;
 
         c
     }
 
     #[inline(always)]
-    fn peek(&self) -> u8 {
-        if self.is_at_end() { 0 } else { self.src[self.current] }
+    fn peek(&self) -> char {
+        self.src[self.current..].chars().next().unwrap_or('\0')
     }
 
     #[inline(always)]
-    fn peek_next(&self) -> u8 {
-        if self.current + 1 >= self.src.len() { 0 } else { self.src[self.current + 1] }
+    fn peek_next(&self) -> char {
+        self.src[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     #[inline(always)]
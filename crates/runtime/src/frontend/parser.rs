@@ -1,52 +1,174 @@
 use crate::{
-    frontend::token::{Token, TokenKind},
+    error::CodaError,
+    frontend::token::{Span, Token, TokenKind},
     runtime::ast::*,
+    utils::{DEFAULT_STACK_LIMIT, StackGuard},
 };
 
-pub fn parse(tokens: Vec<Token>) -> Result<Vec<Stmt>, String> {
-    let mut parser = Parser::new(tokens);
+// fails with the first syntax error. use `parse_recovering` to get all of them
+pub fn parse(tokens: Vec<Token>) -> Result<Vec<Stmt>, CodaError> {
+    let (statements, errors) = parse_recovering(tokens);
 
-    parser.parse()
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(statements),
+    }
+}
+
+// keeps going after a syntax error, returning every error found along with
+// the statements that did parse. statements with errors in them are dropped
+pub fn parse_recovering(tokens: Vec<Token>) -> (Vec<Stmt>, Vec<CodaError>) {
+    parse_within(tokens, StackGuard::new(DEFAULT_STACK_LIMIT))
+}
+
+// `parse_recovering` with a stack budget other than the default, for hosts
+// running scripts on a bigger stack, and for modules imported while a script
+// is already using some of it
+pub fn parse_within(tokens: Vec<Token>, stack: StackGuard) -> (Vec<Stmt>, Vec<CodaError>) {
+    let mut parser = Parser::new(tokens, stack);
+    let statements = parser.parse();
+
+    (statements, parser.errors)
 }
 
+// how deep expressions and blocks can nest. a chain like `a + b + c` is one
+// level however long it is. on a small stack the parser's own stack guard may
+// stop it sooner
+pub const MAX_NESTING: usize = 256;
+
+// how deep chains of binary operators can make the tree, since `a + b + c`
+// nests each `+` inside the next. the passes after the parser guard their own
+// stack, but dropping or cloning the tree recurses all the way down too
+pub const MAX_OPERATOR_DEPTH: usize = 4096;
+
 struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    // how many blocks deep we are, so recovery knows whether a `}` closes one
+    depth: usize,
+    // how deep the expression or block being parsed is, see `MAX_NESTING`
+    nesting: usize,
+    // how many binary operators deep the tree built since the innermost chain
+    // started goes, see `MAX_OPERATOR_DEPTH`. each chain starts it from zero
+    // and puts back the larger of its own depth and what it found there
+    operator_depth: usize,
+    stack: StackGuard,
+    errors: Vec<CodaError>,
 }
 
 impl Parser {
     #[inline(always)]
-    fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+    fn new(tokens: Vec<Token>, stack: StackGuard) -> Self {
+        Self {
+            tokens,
+            current: 0,
+            depth: 0,
+            nesting: 0,
+            operator_depth: 0,
+            stack,
+            errors: Vec::new(),
+        }
     }
 
-    fn parse(&mut self) -> Result<Vec<Stmt>, String> {
+    fn parse(&mut self) -> Vec<Stmt> {
         let mut statements = Vec::new();
 
         while !self.is_at_end() {
-            statements.push(self.statement()?);
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
         }
 
-        Ok(statements)
+        statements
+    }
+
+    // parses a statement, recording the error and skipping ahead to the next
+    // statement if it fails
+    fn declaration(&mut self) -> Option<Stmt> {
+        let start = self.current;
+        let nesting = self.nesting;
+
+        match self.statement() {
+            Ok(stmt) => Some(stmt),
+
+            Err(err) => {
+                self.errors.push(err);
+                self.nesting = nesting;
+
+                // always make progress, even if the error was on the first token
+                if self.current == start {
+                    self.advance();
+                }
+
+                self.synchronize();
+
+                None
+            }
+        }
     }
 
-    fn statement(&mut self) -> Result<Stmt, String> {
+    // skips tokens until something that can start (or, inside a block, end)
+    // a statement. blocks opened while skipping are skipped whole, so a broken
+    // function header doesn't spill its body into the enclosing scope
+    fn synchronize(&mut self) {
+        let mut nesting = 0usize;
+
+        while !self.is_at_end() {
+            match self.peek().kind {
+                TokenKind::Let
+                | TokenKind::Const
+                | TokenKind::Fn
+                | TokenKind::If
+                | TokenKind::While
+                | TokenKind::For
+                | TokenKind::Break
+                | TokenKind::Continue
+                | TokenKind::Return
+                | TokenKind::Try
+                | TokenKind::Throw
+                | TokenKind::Export
+                | TokenKind::Import
+                    if nesting == 0 =>
+                {
+                    return;
+                }
+
+                TokenKind::RBrace if nesting == 0 && self.depth > 0 => return,
+                TokenKind::RBrace => nesting = nesting.saturating_sub(1),
+                TokenKind::LBrace => nesting += 1,
+
+                _ => {}
+            }
+
+            self.advance();
+        }
+    }
+
+    fn statement(&mut self) -> Result<Stmt, CodaError> {
+        let start = self.peek().span;
+        let kind = self.statement_kind()?;
+
+        Ok(Stmt::new(kind, self.span_from(start)))
+    }
+
+    fn statement_kind(&mut self) -> Result<StmtKind, CodaError> {
         let mut is_exported = false;
+        let doc = self.peek().doc.clone();
 
         if self.match_kind(&[TokenKind::Export]) {
             is_exported = true;
         }
 
         if self.match_kind(&[TokenKind::Let]) {
-            return self.let_statement(false, is_exported);
+            return self.let_statement(false, is_exported, doc);
         }
 
         if self.match_kind(&[TokenKind::Const]) {
-            return self.let_statement(true, is_exported);
+            return self.let_statement(true, is_exported, doc);
         }
 
         if self.match_kind(&[TokenKind::Fn]) {
-            return self.fn_statement(is_exported);
+            return self.fn_statement(is_exported, doc);
         }
 
         if self.match_kind(&[TokenKind::Import]) {
@@ -60,25 +182,82 @@ impl Parser {
         if self.match_kind(&[TokenKind::While]) {
             return self.while_statement();
         }
+
+        if self.match_kind(&[TokenKind::For]) {
+            return self.for_statement();
+        }
+
+        if self.match_kind(&[TokenKind::Break]) {
+            return Ok(StmtKind::Break);
+        }
+
+        if self.match_kind(&[TokenKind::Continue]) {
+            return Ok(StmtKind::Continue);
+        }
         
         if self.match_kind(&[TokenKind::Return]) {
             return self.return_statement();
         }
+
+        if self.match_kind(&[TokenKind::Try]) {
+            return self.try_statement();
+        }
+
+        if self.match_kind(&[TokenKind::Throw]) {
+            return Ok(StmtKind::Throw(self.expression()?));
+        }
         
         if self.match_kind(&[TokenKind::LBrace]) {
-            return Ok(Stmt::Block(self.block()?));
+            return Ok(StmtKind::Block(self.block()?));
         }
 
         let expr = self.expression()?;
         
-        Ok(Stmt::Expr(expr))
+        Ok(StmtKind::Expr(expr))
     }
 
-    fn import_statement(&mut self) -> Result<Stmt, String> {
+    // `as` and `from` only mean something here, so they aren't reserved
+    fn import_statement(&mut self) -> Result<StmtKind, CodaError> {
+        if self.match_kind(&[TokenKind::LBrace]) {
+            let mut names = Vec::new();
+
+            while !self.check(&TokenKind::RBrace) {
+                let name = self.identifier("expected name to import")?;
+                let alias = if self.match_contextual("as") { self.identifier("expected name after 'as'")? } else { name.clone() };
+
+                names.push((name, alias));
+
+                if !self.match_kind(&[TokenKind::Comma]) {
+                    break;
+                }
+            }
+
+            self.consume(TokenKind::RBrace, "expected '}' after imported names")?;
+
+            if !self.match_contextual("from") {
+                return Err(self.error_at(self.peek(), "expected 'from' after imported names"));
+            }
+
+            let path = self.module_path()?;
+
+            return Ok(StmtKind::Import(Import { path, names: ImportNames::Only(names) }));
+        }
+
+        let path = self.module_path()?;
+        let names = if self.match_contextual("as") {
+            ImportNames::Namespace(self.identifier("expected name after 'as'")?)
+        } else {
+            ImportNames::All
+        };
+
+        Ok(StmtKind::Import(Import { path, names }))
+    }
+
+    fn module_path(&mut self) -> Result<String, CodaError> {
         let token = self.advance().clone();
 
         match token.kind {
-            TokenKind::String(s) => Ok(Stmt::Import(s)),
+            TokenKind::String(s) => Ok(s),
 
             TokenKind::Identifier(id) => {
                 let mut full_path = id;
@@ -87,97 +266,242 @@ impl Parser {
                     match self.advance().kind.clone() {
                         TokenKind::Identifier(next_id) => full_path.push_str(&format!(".{}", next_id)),
                         
-                        _ => return Err("expected identifier after '.'".into()),
+                        _ => return Err(self.error_at(self.previous(), "expected identifier after '.'")),
                     }
                 }
 
-                Ok(Stmt::Import(full_path))
+                Ok(full_path)
             }
 
-            _ => Err("expected module path or string after import".into()),
+            _ => Err(self.error_at(&token, "expected module path or string after import")),
         }
     }
 
-    fn block(&mut self) -> Result<Vec<Stmt>, String> {
+    fn block(&mut self) -> Result<Vec<Stmt>, CodaError> {
         let mut statements = Vec::new();
 
+        self.nest()?;
+        self.depth += 1;
+
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
-            statements.push(self.statement()?);
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
         }
 
+        self.depth -= 1;
+        self.nesting -= 1;
+
         self.consume(TokenKind::RBrace, "expected '}' after block")?;
 
         Ok(statements)
     }
 
-    fn while_statement(&mut self) -> Result<Stmt, String> {
+    fn while_statement(&mut self) -> Result<StmtKind, CodaError> {
         let condition = self.expression()?;
 
         self.consume(TokenKind::LBrace, "expected '{' after condition")?;
 
         let body = self.block()?;
 
-        Ok(Stmt::While { condition, body })
+        Ok(StmtKind::While { condition, body })
     }
 
-    fn return_statement(&mut self) -> Result<Stmt, String> {
+    fn for_statement(&mut self) -> Result<StmtKind, CodaError> {
+        if let TokenKind::Identifier(name) = &self.peek().kind
+            && self.peek_next().kind == TokenKind::In
+        {
+            let name = name.clone();
+
+            self.advance();
+            self.advance();
+
+            let iterable = self.expression()?;
+
+            self.consume(TokenKind::LBrace, "expected '{' after for-in iterable")?;
+
+            let body = self.block()?;
+
+            return Ok(StmtKind::ForIn {
+                name,
+                slot: Slot::default(),
+                iterable,
+                body,
+            });
+        }
+
+        let initializer = if self.match_kind(&[TokenKind::Semicolon]) {
+            None
+        } else {
+            let stmt = self.statement()?;
+
+            if !matches!(stmt.kind, StmtKind::Let { .. } | StmtKind::Expr(_)) {
+                return Err(CodaError::parse("expected `let` or an expression in for initializer", stmt.span));
+            }
+
+            self.consume(TokenKind::Semicolon, "expected ';' after for initializer")?;
+
+            Some(Box::new(stmt))
+        };
+
+        let condition = if self.check(&TokenKind::Semicolon) { None } else { Some(self.expression()?) };
+
+        self.consume(TokenKind::Semicolon, "expected ';' after for condition")?;
+
+        let increment = if self.check(&TokenKind::LBrace) { None } else { Some(self.expression()?) };
+
+        self.consume(TokenKind::LBrace, "expected '{' after for clauses")?;
+
+        let body = self.block()?;
+
+        Ok(StmtKind::For {
+            initializer,
+            condition,
+            increment,
+            body,
+        })
+    }
+
+    fn return_statement(&mut self) -> Result<StmtKind, CodaError> {
         if self.check(&TokenKind::RBrace) {
-            return Ok(Stmt::Return(None));
+            return Ok(StmtKind::Return(None));
         }
 
         let value = self.expression()?;
 
-        Ok(Stmt::Return(Some(value)))
+        Ok(StmtKind::Return(Some(value)))
+    }
+
+    fn try_statement(&mut self) -> Result<StmtKind, CodaError> {
+        self.consume(TokenKind::LBrace, "expected '{' after try")?;
+
+        let body = self.block()?;
+
+        let catch = if self.match_kind(&[TokenKind::Catch]) {
+            self.consume(TokenKind::LParen, "expected '(' after catch")?;
+
+            let name = self.identifier("expected error name")?;
+
+            self.consume(TokenKind::RParen, "expected ')' after error name")?;
+            self.consume(TokenKind::LBrace, "expected '{' after catch")?;
+
+            Some(Catch {
+                name,
+                slot: Slot::default(),
+                body: self.block()?,
+            })
+        } else {
+            None
+        };
+
+        let finally = if self.match_kind(&[TokenKind::Finally]) {
+            self.consume(TokenKind::LBrace, "expected '{' after finally")?;
+
+            Some(self.block()?)
+        } else {
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            return Err(self.error_at(self.peek(), "expected `catch` or `finally` after try block"));
+        }
+
+        Ok(StmtKind::Try { body, catch, finally })
     }
 
-    fn let_statement(&mut self, is_const: bool, is_exported: bool) -> Result<Stmt, String> {
+    fn let_statement(&mut self, is_const: bool, is_exported: bool, doc: Option<String>) -> Result<StmtKind, CodaError> {
         let name = match self.advance().kind.clone() {
             TokenKind::Identifier(s) => s,
             
-            _ => return Err("expected identifier".into()),
+            _ => return Err(self.error_at(self.previous(), "expected identifier")),
         };
 
         self.consume(TokenKind::Equal, "expected '=' after variable name")?;
 
         let value = self.expression()?;
 
-        Ok(Stmt::Let { name, value, is_const, is_exported })
+        Ok(StmtKind::Let {
+            name,
+            slot: Slot::default(),
+            value,
+            is_const,
+            is_exported,
+            doc,
+        })
     }
 
-    fn fn_statement(&mut self, is_exported: bool) -> Result<Stmt, String> {
+    fn fn_statement(&mut self, is_exported: bool, doc: Option<String>) -> Result<StmtKind, CodaError> {
         let name = match self.advance().kind.clone() {
             TokenKind::Identifier(s) => s,
             
-            _ => return Err("expected function name".into()),
+            _ => return Err(self.error_at(self.previous(), "expected function name")),
         };
 
         self.consume(TokenKind::LParen, "expected '(' after function name")?;
+
+        let params = self.params()?;
+        self.consume(TokenKind::LBrace, "expected '{' before function body")?;
         
-        let mut params = Vec::new();
+        let body = self.block()?;
+
+        Ok(StmtKind::Function {
+            name,
+            slot: Slot::default(),
+            params,
+            body,
+            locals: 0,
+            is_exported,
+            doc,
+        })
+    }
+
+    // the parameter list after the `(`, up to and including the `)`. params
+    // with defaults come after those without, and a rest param comes last
+    fn params(&mut self) -> Result<Vec<Param>, CodaError> {
+        let mut params: Vec<Param> = Vec::new();
 
         if !self.check(&TokenKind::RParen) {
             loop {
-                match self.advance().kind.clone() {
-                    TokenKind::Identifier(s) => params.push(s),
-                    
-                    _ => return Err("expected parameter name".into()),
+                if params.last().is_some_and(|p| p.is_rest) {
+                    return Err(self.error_at(self.peek(), "the rest parameter must be the last one"));
                 }
-                
-                if !self.match_kind(&[TokenKind::Comma]) {
+
+                let is_rest = self.match_kind(&[TokenKind::DotDotDot]);
+
+                let name = match self.advance().kind.clone() {
+                    TokenKind::Identifier(s) => s,
+
+                    _ => return Err(self.error_at(self.previous(), "expected parameter name")),
+                };
+
+                let default = if self.match_kind(&[TokenKind::Equal]) {
+                    if is_rest {
+                        return Err(self.error_at(self.previous(), "the rest parameter can't have a default value"));
+                    }
+
+                    Some(self.expression()?)
+                } else {
+                    None
+                };
+
+                if default.is_none() && !is_rest && params.iter().any(|p| p.default.is_some()) {
+                    return Err(self.error_at(self.previous(), &format!("parameter `{name}` needs a default value, since one before it has one")));
+                }
+
+                params.push(Param { name, default, is_rest });
+
+                if !self.match_kind(&[TokenKind::Comma]) || self.check(&TokenKind::RParen) {
                     break;
                 }
             }
         }
 
         self.consume(TokenKind::RParen, "expected ')' after parameters")?;
-        self.consume(TokenKind::LBrace, "expected '{' before function body")?;
-        
-        let body = self.block()?;
 
-        Ok(Stmt::Function { name, params, body, is_exported })
+        Ok(params)
     }
 
-    fn if_statement(&mut self) -> Result<Stmt, String> {
+    fn if_statement(&mut self) -> Result<StmtKind, CodaError> {
         let condition = self.expression()?;
 
         self.consume(TokenKind::LBrace, "expected '{' after condition")?;
@@ -192,89 +516,249 @@ impl Parser {
             None
         };
 
-        Ok(Stmt::If { condition, then_branch, else_branch })
+        Ok(StmtKind::If { condition, then_branch, else_branch })
+    }
+
+    fn expression(&mut self) -> Result<Expr, CodaError> {
+        self.nested(Self::assignment)
+    }
+
+    fn or(&mut self) -> Result<Expr, CodaError> {
+        let outer = std::mem::take(&mut self.operator_depth);
+        let mut expr = self.and()?;
+
+        while self.match_kind(&[TokenKind::Or]) {
+            let operator = self.previous().kind.clone();
+            let right = self.and()?;
+
+            let span = expr.span.to(right.span);
+
+            self.link(span)?;
+
+            expr = Expr::new(
+                ExprKind::Logical {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
+        }
+
+        self.operator_depth = self.operator_depth.max(outer);
+
+        Ok(expr)
     }
 
-    fn expression(&mut self) -> Result<Expr, String> {
-        self.assignment()
+    fn and(&mut self) -> Result<Expr, CodaError> {
+        let outer = std::mem::take(&mut self.operator_depth);
+        let mut expr = self.equality()?;
+
+        while self.match_kind(&[TokenKind::And]) {
+            let operator = self.previous().kind.clone();
+            let right = self.equality()?;
+
+            let span = expr.span.to(right.span);
+
+            self.link(span)?;
+
+            expr = Expr::new(
+                ExprKind::Logical {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
+        }
+
+        self.operator_depth = self.operator_depth.max(outer);
+
+        Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr, String> {
+    fn equality(&mut self) -> Result<Expr, CodaError> {
+        let outer = std::mem::take(&mut self.operator_depth);
         let mut expr = self.comparison()?;
 
         while self.match_kind(&[TokenKind::EqualEqual, TokenKind::BangEqual]) {
             let operator = self.previous().kind.clone();
             let right = self.comparison()?;
 
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            let span = expr.span.to(right.span);
+
+            self.link(span)?;
+
+            expr = Expr::new(
+                ExprKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
+        self.operator_depth = self.operator_depth.max(outer);
+
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr, String> {
-        let mut expr = self.term()?;
+    fn comparison(&mut self) -> Result<Expr, CodaError> {
+        let outer = std::mem::take(&mut self.operator_depth);
+        let mut expr = self.range()?;
 
         while self.match_kind(&[TokenKind::Greater, TokenKind::GreaterEqual, TokenKind::Less, TokenKind::LessEqual]) {
+            let operator = self.previous().kind.clone();
+            let right = self.range()?;
+
+            let span = expr.span.to(right.span);
+
+            self.link(span)?;
+
+            expr = Expr::new(
+                ExprKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
+        }
+
+        self.operator_depth = self.operator_depth.max(outer);
+
+        Ok(expr)
+    }
+
+    // `start..end`, end exclusive. doesn't chain
+    fn range(&mut self) -> Result<Expr, CodaError> {
+        let expr = self.term()?;
+
+        if self.match_kind(&[TokenKind::DotDot]) {
             let operator = self.previous().kind.clone();
             let right = self.term()?;
 
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            let span = expr.span.to(right.span);
+
+            return Ok(Expr::new(
+                ExprKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            ));
         }
 
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr, String> {
+    fn term(&mut self) -> Result<Expr, CodaError> {
+        let outer = std::mem::take(&mut self.operator_depth);
         let mut expr = self.factor()?;
 
         while self.match_kind(&[TokenKind::Plus, TokenKind::Minus]) {
             let operator = self.previous().kind.clone();
             let right = self.factor()?;
             
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            let span = expr.span.to(right.span);
+
+            self.link(span)?;
+
+            expr = Expr::new(
+                ExprKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
+        self.operator_depth = self.operator_depth.max(outer);
+
         Ok(expr)
     }
 
-    fn factor(&mut self) -> Result<Expr, String> {
+    fn factor(&mut self) -> Result<Expr, CodaError> {
+        let outer = std::mem::take(&mut self.operator_depth);
         let mut expr = self.unary()?;
 
-        while self.match_kind(&[TokenKind::Star, TokenKind::Slash]) {
+        while self.match_kind(&[TokenKind::Star, TokenKind::Slash, TokenKind::Percent]) {
             let operator = self.previous().kind.clone();
             let right = self.unary()?;
             
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            let span = expr.span.to(right.span);
+
+            self.link(span)?;
+
+            expr = Expr::new(
+                ExprKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
+        self.operator_depth = self.operator_depth.max(outer);
+
         Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Expr, String> {
-        self.call()
+    fn unary(&mut self) -> Result<Expr, CodaError> {
+        if self.match_kind(&[TokenKind::Minus, TokenKind::Bang]) {
+            let token = self.previous().clone();
+            let operand = self.nested(Self::unary)?;
+
+            let span = token.span.to(operand.span);
+
+            return Ok(Expr::new(
+                ExprKind::Unary {
+                    operator: token.kind,
+                    operand: Box::new(operand),
+                },
+                span,
+            ));
+        }
+
+        self.power()
     }
 
-    fn call(&mut self) -> Result<Expr, String> {
+    // `**` binds tighter than unary minus and groups to the right, so
+    // `-2 ** 2` is -4 and `2 ** 3 ** 2` is 2 ** 9
+    fn power(&mut self) -> Result<Expr, CodaError> {
+        let expr = self.call()?;
+
+        if self.match_kind(&[TokenKind::StarStar]) {
+            let operator = self.previous().kind.clone();
+            let right = self.nested(Self::unary)?;
+
+            let span = expr.span.to(right.span);
+
+            return Ok(Expr::new(
+                ExprKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            ));
+        }
+
+        Ok(expr)
+    }
+
+    fn call(&mut self) -> Result<Expr, CodaError> {
         let mut expr = self.primary()?;
 
+        let nesting = self.nesting;
+
         loop {
+            self.nest()?;
+
             if self.match_kind(&[TokenKind::LParen]) {
                 let mut args = Vec::new();
 
@@ -289,25 +773,79 @@ impl Parser {
 
                 self.consume(TokenKind::RParen, "expected ')' after arguments")?;
 
-                expr = Expr::Call { callee: Box::new(expr), args };
+                let span = expr.span.to(self.previous().span);
+
+                expr = Expr::new(ExprKind::Call { callee: Box::new(expr), args }, span);
+            } else if self.match_kind(&[TokenKind::LBracket]) {
+                expr = self.index(expr)?;
+            } else if self.match_kind(&[TokenKind::Dot]) {
+                // `obj.field` is sugar for `obj["field"]`
+                let field = match self.advance().kind.clone() {
+                    TokenKind::Identifier(field) => field,
+
+                    _ => return Err(self.error_at(self.previous(), "expected field name after '.'")),
+                };
+
+                let field = Expr::new(ExprKind::Literal(ValueLiteral::String(field)), self.previous().span);
+                let span = expr.span.to(field.span);
+
+                expr = Expr::new(
+                    ExprKind::Index {
+                        target: Box::new(expr),
+                        index: Box::new(field),
+                    },
+                    span,
+                );
             } else {
                 break;
             }
         }
 
+        self.nesting = nesting;
+
         Ok(expr)
     }
 
-    fn primary(&mut self) -> Result<Expr, String> {
-        let token = self.advance().clone();
+    // `target[index]` or a slice `target[start:end]` with either bound optional
+    fn index(&mut self, target: Expr) -> Result<Expr, CodaError> {
+        let span = target.span;
+        let start = if self.check(&TokenKind::Colon) { None } else { Some(Box::new(self.expression()?)) };
 
-        match token.kind {
-            TokenKind::Number(n) => Ok(Expr::Literal(ValueLiteral::Number(n))),
-            TokenKind::String(s) => Ok(Expr::Literal(ValueLiteral::String(s))),
-            TokenKind::True => Ok(Expr::Literal(ValueLiteral::Bool(true))),
-            TokenKind::False => Ok(Expr::Literal(ValueLiteral::Bool(false))),
-            TokenKind::Null => Ok(Expr::Literal(ValueLiteral::Null)),
-            TokenKind::Identifier(s) => Ok(Expr::Variable(s)),
+        let kind = if self.match_kind(&[TokenKind::Colon]) {
+            let end = if self.check(&TokenKind::RBracket) { None } else { Some(Box::new(self.expression()?)) };
+
+            ExprKind::Slice {
+                target: Box::new(target),
+                start,
+                end,
+            }
+        } else {
+            ExprKind::Index {
+                target: Box::new(target),
+                index: start.expect("an index without a colon always has an expression"),
+            }
+        };
+
+        self.consume(TokenKind::RBracket, "expected ']' after index")?;
+
+        Ok(Expr::new(kind, span.to(self.previous().span)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, CodaError> {
+        let start = self.current;
+        // taken before advancing, which stays put at EOF
+        let token = self.peek().clone();
+
+        self.advance();
+
+        let kind = match token.kind {
+            TokenKind::Int(n) => ExprKind::Literal(ValueLiteral::Int(n)),
+            TokenKind::Number(n) => ExprKind::Literal(ValueLiteral::Number(n)),
+            TokenKind::String(s) => ExprKind::Literal(ValueLiteral::String(s)),
+            TokenKind::True => ExprKind::Literal(ValueLiteral::Bool(true)),
+            TokenKind::False => ExprKind::Literal(ValueLiteral::Bool(false)),
+            TokenKind::Null => ExprKind::Literal(ValueLiteral::Null),
+            TokenKind::Identifier(name) => ExprKind::Variable { name, slot: Slot::default() },
 
             TokenKind::Fn => {
                 let name = if let TokenKind::Identifier(_) = self.peek().kind {
@@ -317,34 +855,22 @@ impl Parser {
                 };
 
                 self.consume(TokenKind::LParen, "expected '(' after function name")?;
-                
-                let mut params = Vec::new();
 
-                if !self.check(&TokenKind::RParen) {
-                    loop {
-                        match self.advance().kind.clone() {
-                            TokenKind::Identifier(s) => params.push(s),
-                            _ => return Err("expected parameter name".into()),
-                        }
-                        if !self.match_kind(&[TokenKind::Comma]) {
-                            break;
-                        }
-                    }
-                }
+                let params = self.params()?;
 
-                self.consume(TokenKind::RParen, "expected ')' after parameters")?;
                 self.consume(TokenKind::LBrace, "expected '{' before function body")?;
                 let body = self.block()?;
 
-                Ok(Expr::Function { name, params, body })
+                ExprKind::Function { name, params, body, locals: 0 }
             }
 
+            // a parenthesised expression keeps the span of what's inside
             TokenKind::LParen => {
                 let expr = self.expression()?;
 
                 self.consume(TokenKind::RParen, "expected ')'")?;
 
-                Ok(expr)
+                return Ok(expr);
             }
 
             TokenKind::LBracket => {
@@ -362,23 +888,83 @@ impl Parser {
 
                 self.consume(TokenKind::RBracket, "expected ']'")?;
 
-                Ok(Expr::Array(elements))
+                ExprKind::Array(elements)
             }
-            _ => Err("unexpected token".into()),
-        }
-    }
 
-    fn assignment(&mut self) -> Result<Expr, String> {
-        let expr = self.equality()?;
+            TokenKind::StringPart(text) => {
+                let mut parts = Vec::new();
+                let mut text = Some((text, token.span));
 
-        if self.match_kind(&[TokenKind::Equal]) {
-            let value = self.assignment()?;
+                loop {
+                    if let Some((text, span)) = text.take().filter(|(text, _)| !text.is_empty()) {
+                        parts.push(Expr::new(ExprKind::Literal(ValueLiteral::String(text)), span));
+                    }
+
+                    parts.push(self.expression()?);
 
-            if let Expr::Variable(name) = expr {
-                return Ok(Expr::Assign { name, value: Box::new(value) });
+                    let next = self.advance().clone();
+
+                    match next.kind {
+                        TokenKind::StringPart(rest) => text = Some((rest, next.span)),
+
+                        TokenKind::StringEnd(rest) => {
+                            if !rest.is_empty() {
+                                parts.push(Expr::new(ExprKind::Literal(ValueLiteral::String(rest)), next.span));
+                            }
+
+                            break;
+                        }
+
+                        _ => return Err(self.error_at(&next, "expected '}' to end string interpolation")),
+                    }
+                }
+
+                ExprKind::Interpolate(parts)
             }
 
-            return Err("Invalid assignment target".into());
+            // `{ name: value, "any key": value }`; a trailing comma is allowed
+            TokenKind::LBrace => {
+                let mut entries = Vec::new();
+
+                while !self.check(&TokenKind::RBrace) {
+                    let key = match self.advance().kind.clone() {
+                        TokenKind::Identifier(key) | TokenKind::String(key) => key,
+
+                        _ => return Err(self.error_at(self.previous(), "expected a name or string as map key")),
+                    };
+
+                    self.consume(TokenKind::Colon, "expected ':' after map key")?;
+
+                    entries.push((key, self.expression()?));
+
+                    if !self.match_kind(&[TokenKind::Comma]) {
+                        break;
+                    }
+                }
+
+                self.consume(TokenKind::RBrace, "expected '}' after map entries")?;
+
+                ExprKind::Map(entries)
+            }
+            // left for recovery to see, since it may be the `}` closing a block
+            _ => {
+                self.current = start;
+
+                return Err(self.error_at(&token, "unexpected token"));
+            }
+        };
+
+        Ok(Expr::new(kind, self.span_from(token.span)))
+    }
+
+    fn assignment(&mut self) -> Result<Expr, CodaError> {
+        let expr = self.or()?;
+
+        if self.match_kind(&[TokenKind::Equal]) {
+            let equals = self.previous().clone();
+            let value = self.expression()?;
+
+            return self.assign(expr, None, value, &equals);
         }
 
         // compound assignments
@@ -391,26 +977,54 @@ impl Parser {
 
         for (compound, operator) in compound_map {
             if self.match_kind(&[compound]) {
-                let value = self.assignment()?;
+                let equals = self.previous().clone();
+                let value = self.expression()?;
 
-                if let Expr::Variable(name) = expr.clone() {
-                    return Ok(Expr::Assign {
-                        name: name.clone(),
-                        value: Box::new(Expr::Binary {
-                            left: Box::new(Expr::Variable(name)),
-                            operator,
-                            right: Box::new(value),
-                        }),
-                    });
-                } else {
-                    return Err("Invalid assignment target".into());
-                }
+                return self.assign(expr, Some(operator), value, &equals);
             }
         }
 
         Ok(expr)
     }
 
+    // builds an assignment to `target`. compound assignments to a variable
+    // become `x = x op value`; index targets apply `operator` in place so the
+    // target and index are only evaluated once
+    fn assign(&self, target: Expr, operator: Option<TokenKind>, value: Expr, equals: &Token) -> Result<Expr, CodaError> {
+        let span = target.span.to(value.span);
+
+        match target.kind {
+            ExprKind::Variable { name, slot } => {
+                let value = match operator {
+                    Some(operator) => Expr::new(
+                        ExprKind::Binary {
+                            left: Box::new(Expr::new(ExprKind::Variable { name: name.clone(), slot }, target.span)),
+                            operator,
+                            right: Box::new(value),
+                        },
+                        span,
+                    ),
+
+                    None => value,
+                };
+
+                Ok(Expr::new(ExprKind::Assign { name, slot, value: Box::new(value) }, span))
+            }
+
+            ExprKind::Index { target, index } => Ok(Expr::new(
+                ExprKind::IndexAssign {
+                    target,
+                    index,
+                    operator,
+                    value: Box::new(value),
+                },
+                span,
+            )),
+
+            _ => Err(self.error_at(equals, "Invalid assignment target")),
+        }
+    }
+
     // utils
     #[inline(always)]
     fn match_kind(&mut self, kinds: &[TokenKind]) -> bool {
@@ -423,6 +1037,25 @@ impl Parser {
         false
     }
 
+    // an identifier that acts as a keyword in one spot, like `as` in imports
+    fn match_contextual(&mut self, word: &str) -> bool {
+        if matches!(&self.peek().kind, TokenKind::Identifier(id) if id == word) {
+            self.advance();
+
+            return true;
+        }
+
+        false
+    }
+
+    fn identifier(&mut self, msg: &str) -> Result<String, CodaError> {
+        match self.advance().kind.clone() {
+            TokenKind::Identifier(name) => Ok(name),
+
+            _ => Err(self.error_at(self.previous(), msg)),
+        }
+    }
+
     #[inline(always)]
     fn check(&self, kind: &TokenKind) -> bool {
         if self.is_at_end() {
@@ -433,16 +1066,60 @@ impl Parser {
     }
 
     #[inline(always)]
-    fn consume(&mut self, kind: TokenKind, msg: &str) -> Result<(), String> {
+    fn consume(&mut self, kind: TokenKind, msg: &str) -> Result<(), CodaError> {
         if self.check(&kind) {
             self.advance();
 
             Ok(())
         } else {
-            Err(msg.into())
+            Err(self.error_at(self.peek(), msg))
         }
     }
 
+    fn nest(&mut self) -> Result<(), CodaError> {
+        self.nesting += 1;
+
+        if self.nesting > MAX_NESTING || self.stack.exceeded() {
+            return Err(self.error_at(self.peek(), "code is nested too deeply"));
+        }
+
+        Ok(())
+    }
+
+    // one more operator in a chain, on top of the deepest of its operands.
+    // `span` is the expression it completes
+    fn link(&mut self, span: Span) -> Result<(), CodaError> {
+        self.operator_depth += 1;
+
+        if self.operator_depth > MAX_OPERATOR_DEPTH {
+            return Err(CodaError::parse("code is nested too deeply", span));
+        }
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, CodaError>) -> Result<T, CodaError> {
+        self.nest()?;
+
+        let result = parse(self);
+
+        self.nesting -= 1;
+
+        result
+    }
+
+    #[inline(always)]
+    fn error_at(&self, token: &Token, msg: &str) -> CodaError {
+        CodaError::parse(msg, token.span)
+    }
+
+    // from `start` to the end of the last token consumed
+    #[inline(always)]
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous().span)
+    }
+
     #[inline(always)]
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
@@ -462,6 +1139,11 @@ impl Parser {
         &self.tokens[self.current]
     }
 
+    #[inline(always)]
+    fn peek_next(&self) -> &Token {
+        &self.tokens[(self.current + 1).min(self.tokens.len() - 1)]
+    }
+
     #[inline(always)]
     fn is_at_end(&self) -> bool {
         matches!(self.peek().kind, TokenKind::EOF)
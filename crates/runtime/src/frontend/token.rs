@@ -1,15 +1,26 @@
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
     // literals
+    Int(i64),
+    // a float
     Number(f64),
     String(String),
+    // the text of an interpolated string up to a `${`. the expression's tokens
+    // follow, then another `StringPart` or a `StringEnd` with the rest
+    StringPart(String),
+    StringEnd(String),
     Identifier(String),
 
     // operators
     Plus,
     Minus,
     Star,
+    StarStar,
     Slash,
+    Percent,
+    Bang,
+    And,
+    Or,
     Equal,
     PlusEqual,
     MinusEqual,
@@ -30,7 +41,10 @@ pub enum TokenKind {
     LBracket,
     RBracket,
     Comma,
+    Colon,
     Dot,
+    DotDot,
+    DotDotDot,
     Semicolon,
 
     // keywords
@@ -40,7 +54,15 @@ pub enum TokenKind {
     If,
     Else,
     While,
+    For,
+    In,
+    Break,
+    Continue,
     Return,
+    Try,
+    Catch,
+    Finally,
+    Throw,
     Import,
     Export,
     True,
@@ -53,5 +75,25 @@ pub enum TokenKind {
 #[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
+    pub span: Span,
+    // the `///` comment right before a `let`, `const`, `fn` or `export`
+    pub doc: Option<String>,
+}
+
+// a range of source text. `start` and `end` are byte offsets (end exclusive);
+// `line` and `column` are 1-based and point at `start`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
     pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    // the span covering both `self` and everything up to the end of `other`
+    #[inline(always)]
+    pub fn to(self, other: Span) -> Span {
+        Span { end: other.end.max(self.end), ..self }
+    }
 }
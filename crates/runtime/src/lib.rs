@@ -3,4 +3,5 @@
 pub mod frontend;
 pub mod runtime;
 pub mod utils;
-pub mod env;
\ No newline at end of file
+pub mod env;
+pub mod error;
\ No newline at end of file
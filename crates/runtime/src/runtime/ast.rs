@@ -1,30 +1,96 @@
-use crate::frontend::token::TokenKind;
+use crate::frontend::token::{Span, TokenKind};
 
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     Literal(ValueLiteral),
-    Variable(String),
-    Binary { left: Box<Expr>, operator: TokenKind, right: Box<Expr> },
-    Call { callee: Box<Expr>, args: Vec<Expr> },
-    Assign { name: String, value: Box<Expr> },
+    Variable {
+        name: String,
+        slot: Slot,
+    },
+    Unary {
+        operator: TokenKind,
+        operand: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        operator: TokenKind,
+        right: Box<Expr>,
+    },
+    // `&&` and `||`, kept apart from `Binary` because they short-circuit
+    Logical {
+        left: Box<Expr>,
+        operator: TokenKind,
+        right: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    Assign {
+        name: String,
+        slot: Slot,
+        value: Box<Expr>,
+    },
+    Index {
+        target: Box<Expr>,
+        index: Box<Expr>,
+    },
+    // `target[index] = value`, or `target[index] op= value` when `operator` is set
+    IndexAssign {
+        target: Box<Expr>,
+        index: Box<Expr>,
+        operator: Option<TokenKind>,
+        value: Box<Expr>,
+    },
+    Slice {
+        target: Box<Expr>,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+    },
     Array(Vec<Expr>),
+    Map(Vec<(String, Expr)>),
+    // `"a ${b} c"`; every part is turned into a string and joined
+    Interpolate(Vec<Expr>),
+
+    Function {
+        name: String,
+        params: Vec<Param>,
+        body: Vec<Stmt>,
+        locals: usize,
+    },
+}
 
-    Function { name: String, params: Vec<String>, body: Vec<Stmt> },
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
-pub enum Stmt {
+pub enum StmtKind {
     Let {
         name: String,
+        slot: Slot,
         value: Expr,
         is_const: bool,
         is_exported: bool,
+        // the `///` comment above the declaration, without the slashes
+        doc: Option<String>,
     },
     Function {
         name: String,
-        params: Vec<String>,
+        slot: Slot,
+        params: Vec<Param>,
         body: Vec<Stmt>,
+        locals: usize,
         is_exported: bool,
+        doc: Option<String>,
     },
     Return(Option<Expr>),
     If {
@@ -36,15 +102,99 @@ pub enum Stmt {
         condition: Expr,
         body: Vec<Stmt>,
     },
+    // `for init; condition; increment { ... }`, any clause may be left out
+    For {
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        increment: Option<Expr>,
+        body: Vec<Stmt>,
+    },
+    ForIn {
+        name: String,
+        slot: Slot,
+        iterable: Expr,
+        body: Vec<Stmt>,
+    },
+    Break,
+    Continue,
+    // at least one of `catch` and `finally` is there
+    Try {
+        body: Vec<Stmt>,
+        catch: Option<Catch>,
+        finally: Option<Vec<Stmt>>,
+    },
+    Throw(Expr),
     Block(Vec<Stmt>),
-    Import(String),
+    Import(Import),
     Expr(Expr),
 }
 
+#[derive(Debug, Clone)]
+pub struct Import {
+    // a file path, or a dotted name like `std.io`
+    pub path: String,
+    pub names: ImportNames,
+}
+
+#[derive(Debug, Clone)]
+pub enum ImportNames {
+    // `import path` binds everything the module exports
+    All,
+    // `import path as name` binds them as fields of one map
+    Namespace(String),
+    // `import { a, b as c } from path`, as (exported name, local name) pairs
+    Only(Vec<(String, String)>),
+}
+
+// `catch (name) { ... }`. `name` is bound to the error value for the body
+#[derive(Debug, Clone)]
+pub struct Catch {
+    pub name: String,
+    pub slot: Slot,
+    pub body: Vec<Stmt>,
+}
+
+// `name`, `name = default` or `...name`. defaults are evaluated on each call
+// that leaves them out, in the function's own scope
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub default: Option<Expr>,
+    pub is_rest: bool,
+}
+
 #[derive(Debug, Clone)]
 pub enum ValueLiteral {
+    Int(i64),
     Number(f64),
     String(String),
     Bool(bool),
     Null,
 }
+
+impl Expr {
+    #[inline(always)]
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl Stmt {
+    #[inline(always)]
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+// where a binding lives, filled in by the resolver. module-level bindings stay
+// in the env by name; everything else is a slot in a function frame, `depth`
+// frames up from the one currently executing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Slot {
+    #[default]
+    Global,
+    Local {
+        depth: usize,
+        index: usize,
+    },
+}
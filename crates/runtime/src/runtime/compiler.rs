@@ -0,0 +1,708 @@
+use crate::{
+    error::CodaError,
+    frontend::token::{Span, TokenKind},
+    runtime::{
+        ast::*,
+        value::{Arity, BinaryOp, UnaryOp, Value},
+    },
+    utils::StackGuard,
+};
+use std::rc::Rc;
+
+// a single vm instruction. operands index into the owning chunk's tables,
+// so an `Op` stays small and `Copy`
+#[derive(Clone, Copy, Debug)]
+pub enum Op {
+    Constant(u32),
+    Pop,
+
+    // name, is_const
+    DefineGlobal(u32, bool),
+    DefineExport(u32, bool),
+    GetGlobal(u32),
+    SetGlobal(u32),
+
+    DefineLocal(u32),
+    GetLocal(u32, u32),
+    SetLocal(u32, u32),
+
+    Unary(UnaryOp),
+    // target, index -> value
+    Index,
+    // target, index, value -> null, combining with the old element first
+    // for compound assignments
+    SetIndex(Option<BinaryOp>),
+    // target, start, end -> slice; open bounds are null
+    Slice,
+    Binary(BinaryOp),
+
+    Jump(u32),
+    JumpIfFalse(u32),
+    // for `&&` and `||`: jump leaving the condition on the stack, or pop it
+    // and fall through to the right operand
+    JumpIfFalseOrPop(u32),
+    JumpIfTrueOrPop(u32),
+    // param index, target: skips a default value when the caller passed that argument
+    JumpIfArg(u32, u32),
+    // expects the iterable and a cursor on the stack. pushes the next item,
+    // or pops both and jumps once there are none left
+    IterNext(u32),
+
+    Call(u32),
+    Return,
+
+    // target: until the matching `EndTry`, an error unwinds back to this
+    // frame and jumps to the target with the error value pushed
+    Try(u32),
+    // the same, but the error is set aside for `Rethrow` instead of pushed
+    TryFinally(u32),
+    EndTry,
+    Throw,
+    Rethrow,
+
+    Array(u32),
+    // pops that many key/value pairs, keys first
+    Map(u32),
+    // pops that many values and joins them into one string
+    Interpolate(u32),
+    Closure(u32),
+    // index into the chunk's imports
+    Import(u32),
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    // source span of each instruction, parallel to `code`
+    pub spans: Vec<Span>,
+    pub constants: Vec<Value>,
+    pub names: Vec<String>,
+    pub protos: Vec<Rc<Proto>>,
+    pub imports: Vec<Import>,
+}
+
+// a compiled function body, turned into a `Value::Function` by `Op::Closure`
+#[derive(Debug)]
+pub struct Proto {
+    pub name: Rc<str>,
+    pub arity: Arity,
+    pub locals: usize,
+    pub chunk: Chunk,
+}
+
+// `statements` must already have been through the resolver; `locals` is the
+// slot count it reported for the top-level frame
+pub fn compile(statements: &[Stmt], locals: usize, stack: StackGuard) -> Result<Rc<Proto>, CodaError> {
+    let mut compiler = Compiler::new(stack, Span::default());
+
+    compiler.block(statements)?;
+    compiler.finish()?;
+
+    Ok(Rc::new(Proto {
+        name: "<script>".into(),
+        arity: Arity::default(),
+        locals,
+        chunk: compiler.chunk,
+    }))
+}
+
+struct Compiler {
+    chunk: Chunk,
+    // span of the node being compiled, recorded against every emitted op
+    span: Span,
+    loops: Vec<Loop>,
+    tries: Vec<Try>,
+    // shared with the compilers of nested functions
+    stack: StackGuard,
+}
+
+// jumps out of a loop body that get patched once the loop is compiled
+#[derive(Default)]
+struct Loop {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+    // values the loop keeps on the stack, which `break` has to pop
+    temporaries: usize,
+    // tries already open when the loop started, which `break` and
+    // `continue` stay inside of
+    tries: usize,
+}
+
+// a `try` whose body or `catch` is being compiled. jumping out of it has to
+// close its handlers and run its `finally` block first
+struct Try {
+    handlers: usize,
+    finally: Option<Vec<Stmt>>,
+}
+
+impl Compiler {
+    fn new(stack: StackGuard, span: Span) -> Self {
+        Self {
+            chunk: Chunk::default(),
+            span,
+            loops: Vec::new(),
+            tries: Vec::new(),
+            stack,
+        }
+    }
+
+    fn block(&mut self, statements: &[Stmt]) -> Result<(), CodaError> {
+        for stmt in statements {
+            self.statement(stmt)?;
+        }
+
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), CodaError> {
+        if self.stack.exceeded() {
+            return Err(CodaError::runtime("code is nested too deeply").at(stmt.span));
+        }
+
+        let previous = std::mem::replace(&mut self.span, stmt.span);
+        let result = self.statement_kind(&stmt.kind);
+
+        self.span = previous;
+
+        result
+    }
+
+    fn statement_kind(&mut self, stmt: &StmtKind) -> Result<(), CodaError> {
+        match stmt {
+            StmtKind::Let {
+                name,
+                slot,
+                value,
+                is_const,
+                is_exported,
+                ..
+            } => {
+                self.expression(value)?;
+                self.define(name, *slot, *is_const, *is_exported)
+            }
+
+            StmtKind::Function {
+                name,
+                slot,
+                params,
+                body,
+                locals,
+                is_exported,
+                ..
+            } => {
+                self.function(name, params, body, *locals)?;
+                self.define(name, *slot, false, *is_exported)
+            }
+
+            StmtKind::Return(expr) => {
+                match expr {
+                    Some(e) => self.expression(e)?,
+                    None => self.constant(Value::Null)?,
+                }
+
+                self.leave_tries(0)?;
+                self.emit(Op::Return);
+
+                Ok(())
+            }
+
+            StmtKind::If { condition, then_branch, else_branch } => {
+                self.expression(condition)?;
+
+                let else_jump = self.emit(Op::JumpIfFalse(0));
+
+                self.block(then_branch)?;
+
+                let end_jump = self.emit(Op::Jump(0));
+
+                self.patch(else_jump)?;
+
+                if let Some(else_branch) = else_branch {
+                    self.block(else_branch)?;
+                }
+
+                self.patch(end_jump)
+            }
+
+            StmtKind::While { condition, body } => {
+                let start = self.chunk.code.len();
+
+                self.expression(condition)?;
+
+                let exit_jump = self.emit(Op::JumpIfFalse(0));
+                let body = self.loop_body(body, 0)?;
+
+                self.patch_all(&body.continues)?;
+                self.emit(Op::Jump(to_operand(start)?));
+                self.patch(exit_jump)?;
+                self.patch_all(&body.breaks)
+            }
+
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                if let Some(initializer) = initializer {
+                    self.statement(initializer)?;
+                }
+
+                let start = self.chunk.code.len();
+                let exit_jump = match condition {
+                    Some(condition) => {
+                        self.expression(condition)?;
+
+                        Some(self.emit(Op::JumpIfFalse(0)))
+                    }
+
+                    None => None,
+                };
+
+                let body = self.loop_body(body, 0)?;
+
+                self.patch_all(&body.continues)?;
+
+                if let Some(increment) = increment {
+                    self.expression(increment)?;
+                    self.emit(Op::Pop);
+                }
+
+                self.emit(Op::Jump(to_operand(start)?));
+
+                if let Some(exit_jump) = exit_jump {
+                    self.patch(exit_jump)?;
+                }
+
+                self.patch_all(&body.breaks)
+            }
+
+            StmtKind::ForIn { slot, iterable, body, .. } => {
+                let Slot::Local { index, .. } = slot else {
+                    unreachable!("for-in variables are always locals");
+                };
+
+                self.expression(iterable)?;
+                self.constant(Value::Int(0))?;
+
+                // errors from stepping the iterable point at it, not the whole loop
+                let span = std::mem::replace(&mut self.span, iterable.span);
+                let start = self.emit(Op::IterNext(0));
+
+                self.span = span;
+
+                self.emit(Op::DefineLocal(to_operand(*index)?));
+
+                let body = self.loop_body(body, 2)?;
+
+                self.patch_all(&body.continues)?;
+                self.emit(Op::Jump(to_operand(start)?));
+                self.patch(start)?;
+                self.patch_all(&body.breaks)
+            }
+
+            StmtKind::Break => {
+                let (temporaries, tries) = self.loops.last().map_or((0, 0), |l| (l.temporaries, l.tries));
+
+                self.leave_tries(tries)?;
+
+                for _ in 0..temporaries {
+                    self.emit(Op::Pop);
+                }
+
+                let jump = self.emit(Op::Jump(0));
+
+                self.innermost_loop().breaks.push(jump);
+
+                Ok(())
+            }
+
+            StmtKind::Continue => {
+                let tries = self.loops.last().map_or(0, |l| l.tries);
+
+                self.leave_tries(tries)?;
+
+                let jump = self.emit(Op::Jump(0));
+
+                self.innermost_loop().continues.push(jump);
+
+                Ok(())
+            }
+
+            StmtKind::Try { body, catch, finally } => {
+                let finally_handler = finally.as_ref().map(|_| self.emit(Op::TryFinally(0)));
+                let catch_handler = catch.as_ref().map(|_| self.emit(Op::Try(0)));
+                let handlers = finally_handler.iter().chain(&catch_handler).count();
+
+                self.try_block(body, handlers, finally)?;
+
+                let mut ends = vec![self.emit(Op::Jump(0))];
+
+                if let (Some(catch), Some(handler)) = (catch, catch_handler) {
+                    let Slot::Local { index, .. } = catch.slot else {
+                        unreachable!("catch bindings are always locals");
+                    };
+
+                    self.patch(handler)?;
+                    self.emit(Op::DefineLocal(to_operand(index)?));
+                    self.try_block(&catch.body, handlers - 1, finally)?;
+
+                    if finally_handler.is_some() {
+                        ends.push(self.emit(Op::Jump(0)));
+                    }
+                }
+
+                // an error with nowhere else to go runs `finally` on its way out
+                if let (Some(finally), Some(handler)) = (finally, finally_handler) {
+                    self.patch(handler)?;
+                    self.block(finally)?;
+                    self.emit(Op::Rethrow);
+                }
+
+                self.patch_all(&ends)
+            }
+
+            StmtKind::Throw(expr) => {
+                self.expression(expr)?;
+                self.emit(Op::Throw);
+
+                Ok(())
+            }
+
+            StmtKind::Block(statements) => self.block(statements),
+
+            StmtKind::Import(import) => {
+                let index = to_operand(self.chunk.imports.len())?;
+
+                self.chunk.imports.push(import.clone());
+                self.emit(Op::Import(index));
+
+                Ok(())
+            }
+
+            StmtKind::Expr(expr) => {
+                self.expression(expr)?;
+                self.emit(Op::Pop);
+
+                Ok(())
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), CodaError> {
+        if self.stack.exceeded() {
+            return Err(CodaError::runtime("code is nested too deeply").at(expr.span));
+        }
+
+        let previous = std::mem::replace(&mut self.span, expr.span);
+        let result = self.expression_kind(&expr.kind);
+
+        self.span = previous;
+
+        result
+    }
+
+    fn expression_kind(&mut self, expr: &ExprKind) -> Result<(), CodaError> {
+        match expr {
+            ExprKind::Literal(lit) => self.constant(match lit {
+                ValueLiteral::Int(n) => Value::Int(*n),
+                ValueLiteral::Number(n) => Value::Number(*n),
+                ValueLiteral::String(s) => Value::String(s.clone()),
+                ValueLiteral::Bool(b) => Value::Bool(*b),
+                ValueLiteral::Null => Value::Null,
+            }),
+
+            ExprKind::Variable { name, slot } => {
+                let op = match slot {
+                    Slot::Local { depth, index } => Op::GetLocal(to_operand(*depth)?, to_operand(*index)?),
+                    Slot::Global => Op::GetGlobal(self.name(name)?),
+                };
+
+                self.emit(op);
+
+                Ok(())
+            }
+
+            ExprKind::Unary { operator, operand } => {
+                let op = UnaryOp::from_token(operator).ok_or_else(|| CodaError::runtime(format!("unsupported operator {operator:?}")))?;
+
+                self.expression(operand)?;
+                self.emit(Op::Unary(op));
+
+                Ok(())
+            }
+
+            ExprKind::Logical { left, operator, right } => {
+                self.expression(left)?;
+
+                let jump = match operator {
+                    TokenKind::And => self.emit(Op::JumpIfFalseOrPop(0)),
+                    _ => self.emit(Op::JumpIfTrueOrPop(0)),
+                };
+
+                self.expression(right)?;
+                self.patch(jump)
+            }
+
+            ExprKind::Binary { left, operator, right } => {
+                let op = BinaryOp::from_token(operator).ok_or_else(|| CodaError::runtime(format!("unsupported operator {operator:?}")))?;
+
+                self.expression(left)?;
+                self.expression(right)?;
+                self.emit(Op::Binary(op));
+
+                Ok(())
+            }
+
+            ExprKind::Call { callee, args } => {
+                self.expression(callee)?;
+
+                for arg in args {
+                    self.expression(arg)?;
+                }
+
+                self.emit(Op::Call(to_operand(args.len())?));
+
+                Ok(())
+            }
+
+            ExprKind::Assign { name, slot, value } => {
+                self.expression(value)?;
+
+                let op = match slot {
+                    Slot::Local { depth, index } => Op::SetLocal(to_operand(*depth)?, to_operand(*index)?),
+                    Slot::Global => Op::SetGlobal(self.name(name)?),
+                };
+
+                self.emit(op);
+
+                Ok(())
+            }
+
+            ExprKind::Index { target, index } => {
+                self.expression(target)?;
+                self.expression(index)?;
+                self.emit(Op::Index);
+
+                Ok(())
+            }
+
+            ExprKind::IndexAssign { target, index, operator, value } => {
+                let op = match operator {
+                    Some(operator) => Some(BinaryOp::from_token(operator).ok_or_else(|| CodaError::runtime(format!("unsupported operator {operator:?}")))?),
+                    None => None,
+                };
+
+                self.expression(target)?;
+                self.expression(index)?;
+                self.expression(value)?;
+                self.emit(Op::SetIndex(op));
+
+                Ok(())
+            }
+
+            ExprKind::Slice { target, start, end } => {
+                self.expression(target)?;
+
+                for bound in [start, end] {
+                    match bound {
+                        Some(bound) => self.expression(bound)?,
+                        None => self.constant(Value::Null)?,
+                    }
+                }
+
+                self.emit(Op::Slice);
+
+                Ok(())
+            }
+
+            ExprKind::Array(elements) => {
+                for el in elements {
+                    self.expression(el)?;
+                }
+
+                self.emit(Op::Array(to_operand(elements.len())?));
+
+                Ok(())
+            }
+
+            ExprKind::Interpolate(parts) => {
+                for part in parts {
+                    self.expression(part)?;
+                }
+
+                self.emit(Op::Interpolate(to_operand(parts.len())?));
+
+                Ok(())
+            }
+
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    self.constant(Value::String(key.clone()))?;
+                    self.expression(value)?;
+                }
+
+                self.emit(Op::Map(to_operand(entries.len())?));
+
+                Ok(())
+            }
+
+            ExprKind::Function { name, params, body, locals } => self.function(name, params, body, *locals),
+        }
+    }
+
+    fn function(&mut self, name: &str, params: &[Param], body: &[Stmt], locals: usize) -> Result<(), CodaError> {
+        let mut compiler = Compiler::new(self.stack, self.span);
+
+        // defaults run first, for whichever params the call left out
+        for (index, param) in params.iter().enumerate() {
+            if let Some(default) = &param.default {
+                let index = to_operand(index)?;
+                let jump = compiler.emit(Op::JumpIfArg(index, 0));
+
+                compiler.expression(default)?;
+                compiler.emit(Op::DefineLocal(index));
+                compiler.patch(jump)?;
+            }
+        }
+
+        compiler.block(body)?;
+        compiler.finish()?;
+
+        let index = to_operand(self.chunk.protos.len())?;
+
+        self.chunk.protos.push(Rc::new(Proto {
+            name: name.into(),
+            arity: Arity::of(params),
+            locals,
+            chunk: compiler.chunk,
+        }));
+
+        self.emit(Op::Closure(index));
+
+        Ok(())
+    }
+
+    // compiles a loop body, returning the `break` and `continue` jumps in it
+    fn loop_body(&mut self, body: &[Stmt], temporaries: usize) -> Result<Loop, CodaError> {
+        self.loops.push(Loop {
+            temporaries,
+            tries: self.tries.len(),
+            ..Default::default()
+        });
+
+        let result = self.block(body);
+        let body = self.loops.pop().expect("loop stack underflow");
+
+        result.map(|_| body)
+    }
+
+    // compiles the body or `catch` of a try with `handlers` of its handlers
+    // still open, then falls out of it: closing them and running `finally`
+    #[inline(always)]
+    fn try_block(&mut self, body: &[Stmt], handlers: usize, finally: &Option<Vec<Stmt>>) -> Result<(), CodaError> {
+        self.tries.push(Try { handlers, finally: finally.clone() });
+
+        let result = self.block(body);
+
+        self.tries.pop();
+        result?;
+        self.leave_try(handlers, finally)
+    }
+
+    // for a jump out of every try above `depth`, innermost first
+    fn leave_tries(&mut self, depth: usize) -> Result<(), CodaError> {
+        let tries = self.tries.split_off(depth);
+        let result = tries.iter().rev().try_for_each(|t| self.leave_try(t.handlers, &t.finally));
+
+        self.tries.extend(tries);
+
+        result
+    }
+
+    fn leave_try(&mut self, handlers: usize, finally: &Option<Vec<Stmt>>) -> Result<(), CodaError> {
+        for _ in 0..handlers {
+            self.emit(Op::EndTry);
+        }
+
+        match finally {
+            Some(finally) => self.block(finally),
+            None => Ok(()),
+        }
+    }
+
+    fn innermost_loop(&mut self) -> &mut Loop {
+        self.loops.last_mut().expect("the resolver keeps `break` and `continue` inside loops")
+    }
+
+    fn define(&mut self, name: &str, slot: Slot, is_const: bool, is_exported: bool) -> Result<(), CodaError> {
+        let op = match slot {
+            Slot::Local { index, .. } => Op::DefineLocal(to_operand(index)?),
+            Slot::Global if is_exported => Op::DefineExport(self.name(name)?, is_const),
+            Slot::Global => Op::DefineGlobal(self.name(name)?, is_const),
+        };
+
+        self.emit(op);
+
+        Ok(())
+    }
+
+    // falling off the end of a body returns null
+    fn finish(&mut self) -> Result<(), CodaError> {
+        self.constant(Value::Null)?;
+        self.emit(Op::Return);
+
+        Ok(())
+    }
+
+    fn constant(&mut self, value: Value) -> Result<(), CodaError> {
+        let index = to_operand(self.chunk.constants.len())?;
+
+        self.chunk.constants.push(value);
+        self.emit(Op::Constant(index));
+
+        Ok(())
+    }
+
+    fn name(&mut self, name: &str) -> Result<u32, CodaError> {
+        if let Some(index) = self.chunk.names.iter().position(|n| n == name) {
+            return to_operand(index);
+        }
+
+        self.chunk.names.push(name.to_string());
+
+        to_operand(self.chunk.names.len() - 1)
+    }
+
+    #[inline(always)]
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.spans.push(self.span);
+        self.chunk.code.len() - 1
+    }
+
+    fn patch_all(&mut self, jumps: &[usize]) -> Result<(), CodaError> {
+        for &at in jumps {
+            self.patch(at)?;
+        }
+
+        Ok(())
+    }
+
+    fn patch(&mut self, at: usize) -> Result<(), CodaError> {
+        let target = to_operand(self.chunk.code.len())?;
+
+        match &mut self.chunk.code[at] {
+            Op::Jump(t) | Op::JumpIfFalse(t) | Op::JumpIfFalseOrPop(t) | Op::JumpIfTrueOrPop(t) | Op::JumpIfArg(_, t) | Op::IterNext(t) | Op::Try(t) | Op::TryFinally(t) => *t = target,
+
+            _ => unreachable!("patched a non-jump instruction"),
+        }
+
+        Ok(())
+    }
+}
+
+#[inline(always)]
+fn to_operand(n: usize) -> Result<u32, CodaError> {
+    u32::try_from(n).map_err(|_| CodaError::runtime("function too large to compile"))
+}
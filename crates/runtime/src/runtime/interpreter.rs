@@ -1,95 +1,211 @@
 use crate::{
-    frontend::token::TokenKind,
-    runtime::{ast::*, value::*},
-    env::Env,
+    error::{CodaError, TraceFrame},
+    frontend::{
+        lexer, parser,
+        token::{Span, TokenKind},
+    },
+    runtime::{
+        ast::*,
+        compiler,
+        loader::{ModuleLoader, ModuleSource},
+        resolver::{self, ImportedNames},
+        value::{self, *},
+        vm::Vm,
+    },
+    env::{Env, Frame},
+    utils::{self, DEFAULT_STACK_LIMIT, StackGuard},
 };
 use std::{cell::RefCell, collections::{HashMap, HashSet}, fmt::Write, rc::Rc};
 
-pub type ModuleLoader = fn(&str, &mut Env) -> Result<bool, Box<dyn std::error::Error>>;
-
+// what a module left behind for importers: its exported bindings as they
+// were when it finished running
 pub struct Module {
     pub exports: HashMap<String, Value>,
+    // exports bound with `const`, which stay const where they're imported
+    pub constants: HashSet<String>,
+}
+
+impl Module {
+    // `everything` exports every binding, for modules that don't use `export`
+    pub fn new(env: &Env, everything: bool) -> Self {
+        let exports: HashMap<_, _> = env
+            .values
+            .iter()
+            .filter(|(name, _)| everything || env.exports.contains_key(*name))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        Self {
+            constants: env.constants.iter().filter(|name| exports.contains_key(*name)).cloned().collect(),
+            exports,
+        }
+    }
+
+    // each export and whether it's const, for the resolver
+    fn names(&self) -> HashMap<String, bool> {
+        self.exports.keys().map(|name| (name.clone(), self.constants.contains(name))).collect()
+    }
 }
 
 pub struct Interpreter {
     pub env: Rc<RefCell<Env>>,
+    pub frame: Rc<Frame>,
     pub base_path: std::path::PathBuf,
-    pub module_loader: Option<ModuleLoader>,
-    pub loaded_modules: HashSet<String>,
+    // directories of the modules being run, innermost last. relative imports
+    // resolve against the top one, or `base_path` in the entry script
+    pub(crate) module_dirs: Vec<std::path::PathBuf>,
+    // roots that bare names like `mylib.strings` are looked up in, in order
+    pub module_paths: Vec<std::path::PathBuf>,
+    pub module_loader: Option<Box<dyn ModuleLoader>>,
+    pub loaded_modules: HashMap<String, Rc<Module>>,
+    // files being imported, outermost first, to catch cycles between them
+    pub(crate) loading: Vec<(String, Rc<str>)>,
+    // modules read ahead of their `import` to learn what they export, by
+    // resolved path
+    pending: HashMap<String, Found>,
+    pub backend: Backend,
+    // the file being run, for tracebacks. functions remember the file they
+    // were defined in
+    pub file: Option<Rc<str>>,
+    // calls deeper than this fail instead of overflowing the stack
+    pub max_call_depth: usize,
+    // bytes of host stack a script may use, counted from the outermost call.
+    // the tree walker can run out of this well before `max_call_depth`
+    pub stack_limit: usize,
+    pub(crate) stack: StackGuard,
+    // every call in progress, from either backend, innermost last
+    pub(crate) call_stack: Vec<StackFrame>,
+}
+
+// where an import's module comes from, found but not yet run
+enum Found {
+    Cached(Rc<Module>),
+    Native(String, Box<dyn FnOnce(&mut Env)>),
+    Source { key: String, file: Rc<str>, src: Vec<u8> },
+}
+
+// a call in progress. `line` is where it currently is, kept up to date at
+// each call it makes; natives have none
+#[derive(Clone, Debug)]
+pub(crate) struct StackFrame {
+    pub function: Rc<str>,
+    pub file: Option<Rc<str>>,
+    pub line: Option<usize>,
+    pub native: bool,
+}
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 512;
+
+// module roots from `CODA_PATH`, separated like `PATH` is on this platform
+pub fn coda_path() -> Vec<std::path::PathBuf> {
+    std::env::var_os("CODA_PATH")
+        .map(|paths| std::env::split_paths(&paths).filter(|path| !path.as_os_str().is_empty()).collect())
+        .unwrap_or_default()
+}
+
+// which engine `run` uses. both share the same env, values and module loading.
+// the vm is the default, as it is in the cli; the tree walker stays around as
+// a reference to check it against
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    Tree,
+    #[default]
+    Vm,
 }
 
 pub enum RuntimeControl {
     Return(Value),
+    Break,
+    Continue,
 }
 
 impl Interpreter {
     pub fn new(
         env: Env,
         base_path: std::path::PathBuf,
-        module_loader: Option<ModuleLoader>,
+        module_loader: Option<Box<dyn ModuleLoader>>,
     ) -> Self {
         Self {
             env: Rc::new(RefCell::new(env)),
+            frame: Frame::new(0, None),
             base_path,
+            module_dirs: Vec::new(),
+            module_paths: Vec::new(),
             module_loader,
-            loaded_modules: HashSet::new(),
+            loaded_modules: HashMap::new(),
+            loading: Vec::new(),
+            pending: HashMap::new(),
+            backend: Backend::default(),
+            file: None,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            stack_limit: DEFAULT_STACK_LIMIT,
+            stack: StackGuard::new(DEFAULT_STACK_LIMIT),
+            call_stack: Vec::new(),
         }
     }
 
     pub fn interpret(
         &mut self,
         statements: Vec<Stmt>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        for stmt in statements {
-            self.execute(stmt)?;
+    ) -> Result<(), CodaError> {
+        for stmt in &statements {
+            if let Some(RuntimeControl::Return(_)) = self.execute(stmt)? {
+                break;
+            }
         }
 
         Ok(())
     }
 
-    pub fn execute(&mut self, stmt: Stmt) -> Result<Option<RuntimeControl>, Box<dyn std::error::Error>> {
+    pub fn execute(&mut self, stmt: &Stmt) -> Result<Option<RuntimeControl>, CodaError> {
+        if self.stack.exceeded() {
+            return Err(CodaError::runtime("stack overflow").at(stmt.span));
+        }
+
+        self.execute_kind(&stmt.kind, stmt.span).map_err(|err| err.at(stmt.span))
+    }
+
+    fn execute_kind(&mut self, stmt: &StmtKind, span: Span) -> Result<Option<RuntimeControl>, CodaError> {
         match stmt {
-            Stmt::Let { name, value, is_exported, .. } => {
+            StmtKind::Let {
+                name,
+                slot,
+                value,
+                is_const,
+                is_exported,
+                ..
+            } => {
                 let val = self.evaluate(value)?;
 
-                if is_exported {
-                    self.env.borrow_mut().define_export(name, val);
-                } else {
-                    self.env.borrow_mut().define(name, val);
-                }
+                self.define(name, *slot, val, *is_const, *is_exported);
 
                 Ok(None)
             }
 
-            Stmt::Expr(expr) => {
+            StmtKind::Expr(expr) => {
                 let _ = self.evaluate(expr)?;
                 Ok(None)
             }
 
-            Stmt::Block(statements) => {
-                self.execute_block(statements, None)?;
-
-                Ok(None)
-            }
+            StmtKind::Block(statements) => self.execute_block(statements),
 
-            Stmt::Function { name, params, body, is_exported, .. } => {
-                let function = Value::Function(Function {
-                    name: name.clone(),
-                    params,
-                    body,
-                    closure: self.env.clone(),
-                });
+            StmtKind::Function {
+                name,
+                slot,
+                params,
+                body,
+                locals,
+                is_exported,
+                ..
+            } => {
+                let function = self.function(name, params, body, *locals);
 
-                if is_exported {
-                    self.env.borrow_mut().define_export(name, function);
-                } else {
-                    self.env.borrow_mut().define(name, function);
-                }
+                self.define(name, *slot, function, false, *is_exported);
 
                 Ok(None)
             }
 
-            Stmt::Return(expr) => {
+            StmtKind::Return(expr) => {
                 let value = match expr {
                     Some(e) => self.evaluate(e)?,
                     None => Value::Null,
@@ -98,243 +214,751 @@ impl Interpreter {
                 Ok(Some(RuntimeControl::Return(value)))
             }
 
-            Stmt::If { condition, then_branch, else_branch } => {
+            StmtKind::If { condition, then_branch, else_branch } => {
                 let cond = self.evaluate(condition)?;
                 let cond_bool = cond.as_bool();
 
                 if cond_bool {
-                    self.execute_block(then_branch, None)?;
+                    self.execute_block(then_branch)
                 } else if let Some(else_branch) = else_branch {
-                    self.execute_block(else_branch, None)?;
+                    self.execute_block(else_branch)
+                } else {
+                    Ok(None)
+                }
+            }
+
+            StmtKind::While { condition, body } => {
+                while self.evaluate(condition)?.as_bool() {
+                    match self.execute_block(body)? {
+                        Some(RuntimeControl::Break) => break,
+                        Some(ctrl @ RuntimeControl::Return(_)) => return Ok(Some(ctrl)),
+                        Some(RuntimeControl::Continue) | None => {}
+                    }
                 }
 
                 Ok(None)
             }
 
-            Stmt::While { condition, body } => {
-                while self.evaluate(condition.clone())?.as_bool() {
-                    if let Some(ctrl) = self.execute_block(body.clone(), None)? {
-                        if let RuntimeControl::Return(_) = ctrl {
-                            return Ok(Some(ctrl));
-                        }
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                if let Some(initializer) = initializer {
+                    self.execute(initializer)?;
+                }
+
+                loop {
+                    if let Some(condition) = condition
+                        && !self.evaluate(condition)?.as_bool()
+                    {
+                        break;
+                    }
+
+                    match self.execute_block(body)? {
+                        Some(RuntimeControl::Break) => break,
+                        Some(ctrl @ RuntimeControl::Return(_)) => return Ok(Some(ctrl)),
+                        Some(RuntimeControl::Continue) | None => {}
+                    }
+
+                    if let Some(increment) = increment {
+                        self.evaluate(increment)?;
                     }
                 }
 
                 Ok(None)
             }
 
-            Stmt::Import(module_path) => {
-                self.execute_import(&module_path)?;
+            StmtKind::ForIn { name, slot, iterable, body } => {
+                let span = iterable.span;
+                let iterable = self.evaluate(iterable)?;
+                let mut cursor = 0;
+
+                while let Some((item, next)) = iterate(&iterable, cursor).map_err(|err| err.at(span))? {
+                    cursor = next;
+
+                    self.define(name, *slot, item, false, false);
+
+                    match self.execute_block(body)? {
+                        Some(RuntimeControl::Break) => break,
+                        Some(ctrl @ RuntimeControl::Return(_)) => return Ok(Some(ctrl)),
+                        Some(RuntimeControl::Continue) | None => {}
+                    }
+                }
 
                 Ok(None)
             }
-        }
-    }
 
-    fn execute_block(&mut self, statements: Vec<Stmt>, env: Option<Rc<RefCell<Env>>>) -> Result<Option<RuntimeControl>, Box<dyn std::error::Error>> {
-        let previous = self.env.clone();
-        let env_to_use = env.unwrap_or_else(|| previous.clone());
+            StmtKind::Break => Ok(Some(RuntimeControl::Break)),
+            StmtKind::Continue => Ok(Some(RuntimeControl::Continue)),
 
-        self.env = env_to_use;
+            StmtKind::Try { body, catch, finally } => {
+                let result = match (self.execute_block(body), catch) {
+                    (Err(err), Some(catch)) => {
+                        let err = self.traced(err);
 
-        for stmt in statements {
-            if let Some(RuntimeControl::Return(val)) = self.execute(stmt)? {
-                self.env = previous;
+                        self.define(&catch.name, catch.slot, err.to_value(), false, false);
+                        self.execute_block(&catch.body)
+                    }
+
+                    (result, _) => result,
+                };
+
+                // the resolver keeps `break`, `continue` and `return` out of
+                // `finally`, so it can only finish normally or fail
+                if let Some(finally) = finally {
+                    self.execute_block(finally)?;
+                }
+
+                result
+            }
+
+            StmtKind::Throw(expr) => Err(CodaError::thrown(self.evaluate(expr)?)),
 
-                return Ok(Some(RuntimeControl::Return(val)));
+            StmtKind::Import(import) => {
+                self.set_line(span.line);
+                self.execute_import(import)?;
+
+                Ok(None)
             }
         }
+    }
 
-        self.env = previous;
+    fn execute_block(&mut self, statements: &[Stmt]) -> Result<Option<RuntimeControl>, CodaError> {
+        for stmt in statements {
+            if let Some(ctrl) = self.execute(stmt)? {
+                return Ok(Some(ctrl));
+            }
+        }
 
         Ok(None)
     }
 
-    fn execute_import(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        // Prevent duplicate imports
-        if self.loaded_modules.contains(path) {
-            return Ok(());
+    // runs `f` with another env and frame active, putting the current ones back
+    // afterwards even if `f` fails
+    fn with_scope<T>(&mut self, env: Rc<RefCell<Env>>, frame: Rc<Frame>, f: impl FnOnce(&mut Self) -> T) -> T {
+        let previous_env = std::mem::replace(&mut self.env, env);
+        let previous_frame = std::mem::replace(&mut self.frame, frame);
+        let result = f(self);
+
+        self.env = previous_env;
+        self.frame = previous_frame;
+
+        result
+    }
+
+    #[inline(always)]
+    fn define(&mut self, name: &str, slot: Slot, value: Value, is_const: bool, is_exported: bool) {
+        match slot {
+            Slot::Local { index, .. } => self.frame.set(0, index, value),
+            Slot::Global => self.env.borrow_mut().declare(name.to_string(), value, is_const, is_exported),
         }
+    }
+
+    fn function(&self, name: &str, params: &[Param], body: &[Stmt], locals: usize) -> Value {
+        Value::Function(Function {
+            name: name.into(),
+            file: self.file.clone(),
+            arity: Arity::of(params),
+            body: FunctionBody::Ast {
+                params: params.into(),
+                body: body.into(),
+            },
+            locals,
+            closure: self.env.clone(),
+            frame: self.frame.clone(),
+        })
+    }
+
+    // binds what `import` asks for from the module, loading it the first time
+    pub fn execute_import(&mut self, import: &Import) -> Result<(), CodaError> {
+        let module = self.load_module(&import.path)?;
+        let mut env = self.env.borrow_mut();
+
+        match &import.names {
+            ImportNames::All => {
+                for (name, value) in &module.exports {
+                    env.declare(name.clone(), value.clone(), module.constants.contains(name), false);
+                }
+            }
+
+            ImportNames::Namespace(alias) => {
+                let mut names: Vec<_> = module.exports.keys().collect();
+                let mut namespace = Map::default();
+
+                names.sort();
+
+                for name in names {
+                    namespace.insert(name.clone(), module.exports[name].clone());
+                }
+
+                env.define_const(alias.clone(), Value::map(namespace));
+            }
+
+            ImportNames::Only(names) => {
+                for (name, alias) in names {
+                    let value = module
+                        .exports
+                        .get(name)
+                        .ok_or_else(|| CodaError::import(format!("module `{}` has no export named `{name}`", import.path)))?;
 
-        // Try external module loader (std etc.)
-        if let Some(loader) = self.module_loader {
-            let handled = loader(path, &mut self.env.borrow_mut())?;
-            if handled {
-                self.loaded_modules.insert(path.to_string());
-                return Ok(());
+                    env.declare(alias.clone(), value.clone(), module.constants.contains(name), false);
+                }
             }
         }
 
-        // Fallback: user file import
-        let full_path = if path.starts_with("./") || path.starts_with("../") {
-            self.base_path.join(path)
+        Ok(())
+    }
+
+    // relative paths are joined onto the importing file's directory first, so
+    // loaders and the cache see one spelling
+    fn resolve_import(&self, path: &str) -> String {
+        if path.starts_with("./") || path.starts_with("../") {
+            let dir = self.module_dirs.last().unwrap_or(&self.base_path);
+
+            utils::normalize_path(&dir.join(path)).display().to_string()
         } else {
-            std::path::PathBuf::from(path)
+            path.to_string()
+        }
+    }
+
+    // loader modules are cached by their resolved path and files by their
+    // canonical path, so every spelling of a file's path shares one copy of it
+    fn load_module(&mut self, path: &str) -> Result<Rc<Module>, CodaError> {
+        let resolved = self.resolve_import(path);
+
+        let found = match self.pending.remove(&resolved) {
+            Some(found) => found,
+            None => self.find_module_source(path, resolved)?,
         };
 
-        let src = std::fs::read_to_string(&full_path)?;
+        match found {
+            Found::Cached(module) => Ok(module),
+            Found::Native(key, register) => Ok(self.register_native(key, register)),
+
+            // another spelling of the path may have loaded it since it was stashed
+            Found::Source { key, .. } if self.loaded_modules.contains_key(&key) => Ok(self.loaded_modules[&key].clone()),
+            Found::Source { key, file, src } => self.load_source(key, file, &src, Rc::new(RefCell::new(Env::new_with_parent(None)))),
+        }
+    }
 
-        let tokens = crate::frontend::lexer::scan(&src)?;
-        let stmts = crate::frontend::parser::parse(tokens)?;
+    // where the module behind a resolved import path comes from, without
+    // running it
+    fn find_module_source(&self, path: &str, resolved: String) -> Result<Found, CodaError> {
+        if let Some(module) = self.loaded_modules.get(&resolved) {
+            return Ok(Found::Cached(module.clone()));
+        }
 
-        let module_env = Rc::new(RefCell::new(Env::new_with_parent(None)));
+        let loaded = match &self.module_loader {
+            Some(loader) => loader.load(&resolved)?,
+            None => None,
+        };
 
-        for stmt in stmts {
-            if let Some(ctrl) =
-                self.execute_block(vec![stmt], Some(module_env.clone()))?
-            {
-                if let RuntimeControl::Return(_) = ctrl {
-                    break;
+        match loaded {
+            Some(ModuleSource::Native(register)) => Ok(Found::Native(resolved, register)),
+
+            Some(ModuleSource::Source(source)) => Ok(Found::Source {
+                file: resolved.as_str().into(),
+                key: resolved,
+                src: source.into_bytes(),
+            }),
+
+            None => {
+                let full_path = self.find_module(&resolved).unwrap_or_else(|| std::path::PathBuf::from(&resolved));
+                let canonical = full_path.canonicalize().map_err(|err| self.unreadable(path, err))?;
+                let key = canonical.display().to_string();
+
+                if let Some(module) = self.loaded_modules.get(&key) {
+                    return Ok(Found::Cached(module.clone()));
                 }
+
+                let src = std::fs::read(&full_path).map_err(|err| CodaError::import(format!("cannot read module `{}`: {err}", full_path.display())))?;
+
+                Ok(Found::Source {
+                    key,
+                    file: full_path.display().to_string().into(),
+                    src,
+                })
             }
         }
+    }
 
-        let values = module_env.borrow().values.clone();
+    fn register_native(&mut self, key: String, register: Box<dyn FnOnce(&mut Env)>) -> Rc<Module> {
+        let mut module_env = Env::new_with_parent(None);
 
-        for (name, val) in values {
-            self.env.borrow_mut().define(name, val);
-        }
+        register(&mut module_env);
 
-        self.loaded_modules.insert(path.to_string());
+        let module = Rc::new(Module::new(&module_env, true));
 
-        Ok(())
+        self.loaded_modules.insert(key, module.clone());
+
+        module
     }
 
-    pub fn evaluate(&mut self, expr: Expr) -> Result<Value, Box<dyn std::error::Error>> {
-        match expr {
-            Expr::Literal(lit) => Ok(match lit {
-                ValueLiteral::Number(n) => Value::Number(n),
-                ValueLiteral::String(s) => Value::String(s),
-                ValueLiteral::Bool(b) => Value::Bool(b),
-                ValueLiteral::Null => Value::Null,
-            }),
+    // the names each plain `import` in `statements` binds, for the resolver to
+    // check against. natives are registered right away, since that has no
+    // effect a script can see; source modules are only parsed, and stashed so
+    // they still run when their `import` is reached. modules that can't be
+    // loaded are left out, and fail when their `import` runs. returns the
+    // resolved paths it stashed
+    fn import_exports(&mut self, statements: &[Stmt]) -> (ImportedNames, Vec<String>) {
+        let mut imported = ImportedNames::new();
+        let mut stashed = Vec::new();
 
-            Expr::Variable(name) => Ok(self.env.borrow().get(&name).ok_or_else(|| format!("undefined variable `{name}`"))?),
+        for stmt in statements {
+            let StmtKind::Import(Import { path, names: ImportNames::All }) = &stmt.kind else {
+                continue;
+            };
 
-            Expr::Binary { left, operator, right } => {
-                let l = self.evaluate(*left)?;
-                let r = self.evaluate(*right)?;
+            let resolved = self.resolve_import(path);
 
-                match (&l, &r, operator.clone()) {
-                    // numbers
-                    (Value::Number(a), Value::Number(b), TokenKind::Plus) => Ok(Value::Number(a + b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::Minus) => Ok(Value::Number(a - b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::Star) => Ok(Value::Number(a * b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::Slash) => Ok(Value::Number(a / b)),
+            let found = match self.pending.remove(&resolved) {
+                Some(found) => found,
+                None => match self.find_module_source(path, resolved.clone()) {
+                    Ok(found) => found,
+                    Err(_) => continue,
+                },
+            };
 
-                    // compound assignment
-                    (Value::Number(a), Value::Number(b), TokenKind::PlusEqual) => Ok(Value::Number(a + b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::MinusEqual) => Ok(Value::Number(a - b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::StarEqual) => Ok(Value::Number(a * b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::SlashEqual) => Ok(Value::Number(a / b)),
+            let exports = match found {
+                Found::Cached(module) => module.names(),
+                Found::Native(key, register) => self.register_native(key, register).names(),
 
-                    // strings
-                    (Value::String(a), Value::String(b), TokenKind::Plus) => {
-                        let mut s = String::with_capacity(a.len() + b.len());
+                Found::Source { key, file, src } => {
+                    let exports = self.exported_names(&src);
 
-                        s.push_str(a);
-                        s.push_str(b);
+                    self.pending.insert(resolved.clone(), Found::Source { key, file, src });
+                    stashed.push(resolved);
 
-                        Ok(Value::String(s))
+                    match exports {
+                        Some(exports) => exports,
+                        None => continue,
                     }
+                }
+            };
 
-                    (Value::String(a), Value::Number(b), TokenKind::Plus) => {
-                        let mut s = String::with_capacity(a.len() + 16);
+            imported.insert(path.clone(), exports);
+        }
 
-                        s.push_str(a);
-                        write!(&mut s, "{}", b).unwrap();
+        (imported, stashed)
+    }
 
-                        Ok(Value::String(s))
-                    }
+    // what a module's source declares with `export`, or `None` if it doesn't
+    // parse
+    fn exported_names(&self, src: &[u8]) -> Option<HashMap<String, bool>> {
+        let tokens = lexer::scan_bytes(src).ok()?;
+        let (stmts, errors) = parser::parse_within(tokens, self.stack);
 
-                    (Value::Number(a), Value::String(b), TokenKind::Plus) => {
-                        let mut s = String::with_capacity(a.to_string().len() + b.len());
+        if !errors.is_empty() {
+            return None;
+        }
 
-                        s.push_str(a.to_string().as_str());
-                        s.push_str(b);
+        let exports = stmts.iter().filter_map(|stmt| match &stmt.kind {
+            StmtKind::Let {
+                name, is_const, is_exported: true, ..
+            } => Some((name.clone(), *is_const)),
+            StmtKind::Function { name, is_exported: true, .. } => Some((name.clone(), false)),
+            _ => None,
+        });
 
-                        Ok(Value::String(s))
-                    }
+        Some(exports.collect())
+    }
+
+    // runs a module's source and caches it under `key`, refusing to start one
+    // that's already part of the import chain
+    fn load_source(&mut self, key: String, file: Rc<str>, src: &[u8], module_env: Rc<RefCell<Env>>) -> Result<Rc<Module>, CodaError> {
+        // the entry script never goes through here, so it joins the chain
+        // when the first import starts
+        let entry = match (self.loading.is_empty(), &self.file) {
+            (true, Some(file)) => {
+                let path = std::path::Path::new(&**file).canonicalize();
 
-                    // comparison operators
-                    (Value::Number(a), Value::Number(b), TokenKind::Greater) => Ok(Value::Bool(a > b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::GreaterEqual) => Ok(Value::Bool(a >= b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::Less) => Ok(Value::Bool(a < b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::LessEqual) => Ok(Value::Bool(a <= b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::EqualEqual) => Ok(Value::Bool(a == b)),
-                    (Value::Number(a), Value::Number(b), TokenKind::BangEqual) => Ok(Value::Bool(a != b)),
-                    (Value::String(a), Value::String(b), TokenKind::EqualEqual) => Ok(Value::Bool(a == b)),
+                Some((path.map_or_else(|_| file.to_string(), |path| path.display().to_string()), file.clone()))
+            }
 
-                    _ => Err(format!("unsupported operation: {l:?} {operator:?} {r:?}").into()),
+            _ => None,
+        };
+
+        let seeded = entry.is_some();
+
+        self.loading.extend(entry);
+
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == key) {
+            let chain: Vec<_> = self.loading[start..].iter().map(|(_, file)| &**file).chain([&*file]).collect();
+            let err = CodaError::import(format!("circular import: {}", chain.join(" -> ")));
+
+            if seeded {
+                self.loading.clear();
+            }
+
+            return Err(err);
+        }
+
+        self.loading.push((key.clone(), file.clone()));
+
+        let result = self.run_module(src, file, module_env.clone());
+
+        self.loading.pop();
+
+        if seeded {
+            self.loading.clear();
+        }
+
+        result?;
+
+        let module = Rc::new(Module::new(&module_env.borrow(), false));
+
+        self.loaded_modules.insert(key, module.clone());
+
+        Ok(module)
+    }
+
+    // `a.b` is `a/b.coda` or `a/b/mod.coda` under the first root that has one
+    fn find_module(&self, name: &str) -> Option<std::path::PathBuf> {
+        let mut segments = name.split('.');
+
+        if !segments.all(|segment| segment.chars().next().is_some_and(utils::is_ident_start) && segment.chars().all(utils::is_ident_continue)) {
+            return None;
+        }
+
+        let relative: std::path::PathBuf = name.split('.').collect();
+
+        self.module_paths.iter().find_map(|root| {
+            let root = root.join(&relative);
+
+            [root.with_extension("coda"), root.join("mod.coda")]
+                .into_iter()
+                .map(|path| utils::normalize_path(&path))
+                .find(|path| path.is_file())
+        })
+    }
+
+    fn unreadable(&self, path: &str, err: std::io::Error) -> CodaError {
+        let err = CodaError::import(format!("cannot read module `{path}`: {err}"));
+
+        match self.module_paths.is_empty() || path.contains('/') {
+            true => err,
+            false => {
+                let roots: Vec<_> = self.module_paths.iter().map(|root| root.display().to_string()).collect();
+
+                err.with_note(format!("searched {}", roots.join(", ")))
+            }
+        }
+    }
+
+    fn run_module(&mut self, src: &[u8], file: Rc<str>, module_env: Rc<RefCell<Env>>) -> Result<(), CodaError> {
+        let tokens = lexer::scan_bytes(src).map_err(|err| err.in_file(file.clone()))?;
+        let (stmts, errors) = parser::parse_within(tokens, self.stack);
+
+        if let Some(err) = errors.into_iter().next() {
+            return Err(err.in_file(file.clone()));
+        }
+
+        let previous = self.file.replace(file.clone());
+
+        self.module_dirs.push(std::path::Path::new(&*file).parent().map(std::path::Path::to_path_buf).unwrap_or_default());
+
+        let result = self.with_scope(module_env, Frame::new(0, None), |this| this.run(stmts));
+
+        self.module_dirs.pop();
+        self.file = previous;
+
+        result.map_err(|err| err.in_file(file))
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, CodaError> {
+        if self.stack.exceeded() {
+            return Err(CodaError::runtime("stack overflow").at(expr.span));
+        }
+
+        self.evaluate_kind(&expr.kind, expr.span).map_err(|err| err.at(expr.span))
+    }
+
+    fn evaluate_kind(&mut self, expr: &ExprKind, span: Span) -> Result<Value, CodaError> {
+        match expr {
+            ExprKind::Literal(lit) => Ok(match lit {
+                ValueLiteral::Int(n) => Value::Int(*n),
+                ValueLiteral::Number(n) => Value::Number(*n),
+                ValueLiteral::String(s) => Value::String(s.clone()),
+                ValueLiteral::Bool(b) => Value::Bool(*b),
+                ValueLiteral::Null => Value::Null,
+            }),
+
+            ExprKind::Variable { name, slot } => match slot {
+                Slot::Local { depth, index } => Ok(self.frame.get(*depth, *index)),
+                Slot::Global => self.env.borrow().get(name).ok_or_else(|| CodaError::runtime(format!("undefined variable `{name}`"))),
+            },
+
+            ExprKind::Unary { operator, operand } => {
+                let v = self.evaluate(operand)?;
+                let op = UnaryOp::from_token(operator).ok_or_else(|| CodaError::runtime(format!("unsupported operation: {operator:?} {v:?}")))?;
+
+                unary(op, &v)
+            }
+
+            // the result is whichever operand decided it, like lua or python
+            ExprKind::Logical { left, operator, right } => {
+                let l = self.evaluate(left)?;
+
+                match operator {
+                    TokenKind::Or if l.as_bool() => Ok(l),
+                    TokenKind::And if !l.as_bool() => Ok(l),
+
+                    _ => self.evaluate(right),
                 }
             }
 
-            Expr::Call { callee, args } => {
-                let callee_val = self.evaluate(*callee)?;
+            ExprKind::Binary { left, operator, right } => {
+                let l = self.evaluate(left)?;
+                let r = self.evaluate(right)?;
+                let op = BinaryOp::from_token(operator).ok_or_else(|| CodaError::runtime(format!("unsupported operation: {l:?} {operator:?} {r:?}")))?;
+
+                binary(&l, op, &r)
+            }
+
+            ExprKind::Call { callee, args } => {
+                let callee_val = self.evaluate(callee)?;
                 let mut evaluated_args = Vec::with_capacity(args.len());
 
                 for arg in args {
                     evaluated_args.push(self.evaluate(arg)?);
                 }
 
-                match callee_val {
-                    Value::NativeFunction(f) => Ok(f(evaluated_args)),
-                    Value::Function(func) => {
-                        let call_env = Rc::new(RefCell::new(Env::new_with_parent(Some(func.closure.clone()))));
+                self.set_line(span.line);
+                self.call(callee_val, evaluated_args)
+            }
 
-                        for (param, arg) in func.params.iter().zip(evaluated_args.into_iter()) {
-                            call_env.borrow_mut().define(param.clone(), arg);
-                        }
+            ExprKind::Assign { name, slot, value } => {
+                let val = self.evaluate(value)?;
 
-                        let result = match self.execute_block(func.body.clone(), Some(call_env))? {
-                            Some(RuntimeControl::Return(val)) => val,
-                            None => Value::Null,
-                        };
+                match slot {
+                    Slot::Local { depth, index } => self.frame.set(*depth, *index, val),
+                    Slot::Global => self.env.borrow_mut().assign(name, val)?,
+                }
 
-                        Ok(result)
-                    }
+                Ok(Value::Null)
+            }
 
-                    _ => panic!("can only call functions"),
-                }
+            ExprKind::Index { target, index } => {
+                let target = self.evaluate(target)?;
+                let index = self.evaluate(index)?;
+
+                value::index(&target, &index)
             }
 
-            Expr::Assign { name, value } => {
-                let val = self.evaluate(*value)?;
+            ExprKind::IndexAssign { target, index, operator, value } => {
+                let target = self.evaluate(target)?;
+                let index = self.evaluate(index)?;
+                let mut val = self.evaluate(value)?;
 
-                self.env.borrow_mut().assign(&name, val)?;
+                if let Some(operator) = operator {
+                    let op = BinaryOp::from_token(operator).ok_or_else(|| CodaError::runtime(format!("unsupported operator {operator:?}")))?;
+
+                    val = binary(&value::index(&target, &index)?, op, &val)?;
+                }
+
+                set_index(&target, &index, val)?;
 
                 Ok(Value::Null)
             }
 
-            Expr::Array(elements) => {
+            ExprKind::Slice { target, start, end } => {
+                let target = self.evaluate(target)?;
+                let start = match start {
+                    Some(start) => self.evaluate(start)?,
+                    None => Value::Null,
+                };
+                let end = match end {
+                    Some(end) => self.evaluate(end)?,
+                    None => Value::Null,
+                };
+
+                slice(&target, &start, &end)
+            }
+
+            ExprKind::Array(elements) => {
                 let mut values = Vec::with_capacity(elements.len());
 
                 for el in elements {
                     values.push(self.evaluate(el)?);
                 }
 
-                Ok(Value::Array(values))
+                Ok(Value::array(values))
+            }
+
+            ExprKind::Interpolate(parts) => {
+                let mut s = String::new();
+
+                for part in parts {
+                    write!(s, "{}", self.evaluate(part)?).unwrap();
+                }
+
+                Ok(Value::String(s))
+            }
+
+            ExprKind::Map(entries) => {
+                let mut map = Map::default();
+
+                for (key, value) in entries {
+                    map.insert(key.clone(), self.evaluate(value)?);
+                }
+
+                Ok(Value::map(map))
             }
 
-            Expr::Function { name, params, body } => {
-                let func = Value::Function(Function {
-                    name: name.clone(),
-                    params,
-                    body,
-                    closure: self.env.clone(),
-                });
+            ExprKind::Function { name, params, body, locals } => Ok(self.function(name, params, body, *locals)),
+        }
+    }
+
+    pub fn call(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, CodaError> {
+        match callee {
+            Value::NativeFunction(native) => {
+                native.arity.check(&native.name, args.len())?;
 
-                Ok(func)
+                self.enter(native.name.clone(), None, true, |this| (native.func)(this, args))
             }
+            Value::Function(func) => match &func.body {
+                FunctionBody::Ast { params, body } => {
+                    func.arity.check(&func.name, args.len())?;
+
+                    let frame = Frame::new(func.locals, Some(func.frame.clone()));
+                    let argc = args.len();
+
+                    func.arity.bind(&frame, args);
+
+                    let result = self.enter(func.name.clone(), func.file.clone(), false, |this| {
+                        this.with_scope(func.closure.clone(), frame, |this| {
+                            for (index, param) in params.iter().enumerate().skip(argc) {
+                                if let Some(default) = &param.default {
+                                    let value = this.evaluate(default)?;
+
+                                    this.frame.set(0, index, value);
+                                }
+                            }
+
+                            this.execute_block(body)
+                        })
+                    });
+
+                    let result = match result? {
+                        Some(RuntimeControl::Return(val)) => val,
+                        // the resolver keeps `break` and `continue` inside loops
+                        Some(RuntimeControl::Break | RuntimeControl::Continue) | None => Value::Null,
+                    };
 
-            expr => panic!("unimplemented expr {expr:?}"),
+                    Ok(result)
+                }
+
+                FunctionBody::Bytecode(_) => Vm::new(self).call(Value::Function(func), args),
+            },
+
+            callee => Err(CodaError::runtime(format!("can only call functions, got {callee:?}"))),
         }
     }
 
+    // runs `f` as a call to `function`, giving any error it returns a trace
+    fn enter<T>(&mut self, function: Rc<str>, file: Option<Rc<str>>, native: bool, f: impl FnOnce(&mut Self) -> Result<T, CodaError>) -> Result<T, CodaError> {
+        self.push_frame(function, file, native)?;
+
+        let result = f(self).map_err(|err| self.traced(err));
+
+        self.call_stack.pop();
+
+        result
+    }
+
+    pub(crate) fn push_frame(&mut self, function: Rc<str>, file: Option<Rc<str>>, native: bool) -> Result<(), CodaError> {
+        // the outermost call is where the script's share of the stack starts
+        if self.call_stack.is_empty() {
+            self.stack = StackGuard::new(self.stack_limit);
+        }
+
+        if self.call_stack.len() >= self.max_call_depth {
+            return Err(CodaError::runtime(format!("maximum call depth of {} exceeded", self.max_call_depth)));
+        }
+
+        if self.stack.exceeded() {
+            return Err(CodaError::runtime("stack overflow"));
+        }
+
+        self.call_stack.push(StackFrame { function, file, line: None, native });
+
+        Ok(())
+    }
+
+    // records where the innermost call currently is, before it calls out
+    #[inline(always)]
+    pub(crate) fn set_line(&mut self, line: usize) {
+        if let Some(frame) = self.call_stack.last_mut() {
+            frame.line = Some(line);
+        }
+    }
+
+    // copies the call stack into `err` the first time it leaves a call. the
+    // innermost function's line is wherever the error itself points
+    pub(crate) fn traced(&self, mut err: CodaError) -> CodaError {
+        if !err.info().trace.is_empty() {
+            return err;
+        }
+
+        let mut trace: Vec<_> = self
+            .call_stack
+            .iter()
+            .map(|frame| TraceFrame {
+                function: if frame.function.is_empty() { "<anonymous>".to_string() } else { frame.function.to_string() },
+                file: frame.file.clone(),
+                line: frame.line,
+            })
+            .collect();
+
+        let span = err.info().span;
+
+        if let (Some(frame), Some(span)) = (trace.last_mut(), span)
+            && !self.call_stack.last().is_some_and(|frame| frame.native)
+        {
+            frame.line = Some(span.line);
+        }
+
+        // a function imported from another module reports its own file
+        if let Some(file) = trace.iter().rev().find_map(|frame| frame.file.clone()) {
+            err = err.in_file(file);
+        }
+
+        err.info_mut().trace = trace.into();
+        err
+    }
+
     pub fn run(
         &mut self,
         statements: Vec<Stmt>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.interpret(statements)
+    ) -> Result<(), CodaError> {
+        self.enter("<script>".into(), self.file.clone(), false, |this| this.run_script(statements))
+    }
+
+    fn run_script(&mut self, mut statements: Vec<Stmt>) -> Result<(), CodaError> {
+        let (imported, stashed) = self.import_exports(&statements);
+
+        let resolved = resolver::resolve(&mut statements, &self.env.borrow(), &imported, self.stack);
+
+        let result = resolved.and_then(|locals| {
+            self.with_scope(self.env.clone(), Frame::new(locals, None), |this| match this.backend {
+                Backend::Tree => this.interpret(statements),
+                Backend::Vm => {
+                    let proto = compiler::compile(&statements, locals, this.stack)?;
+
+                    Vm::new(this).run(proto)?;
+
+                    Ok(())
+                }
+            })
+        });
+
+        // whatever an error kept from being imported is read afresh next time
+        for resolved in stashed {
+            self.pending.remove(&resolved);
+        }
+
+        result
     }
 }
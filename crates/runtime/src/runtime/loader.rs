@@ -0,0 +1,82 @@
+use crate::{env::Env, error::CodaError};
+use std::collections::HashMap;
+
+// what a loader hands back for an import path
+pub enum ModuleSource {
+    // coda source, run like a file would be. relative imports inside it
+    // resolve against the import path's directory
+    Source(String),
+    // registers natives straight into the module's env; everything it
+    // defines is exported
+    Native(Box<dyn FnOnce(&mut Env)>),
+}
+
+// asked for every import before the filesystem is. `Ok(None)` means the path
+// isn't this loader's, so the next loader (or the disk) gets a go. relative
+// paths arrive already joined onto the importing file's directory
+pub trait ModuleLoader {
+    fn load(&self, path: &str) -> Result<Option<ModuleSource>, CodaError>;
+}
+
+impl<F: Fn(&str) -> Result<Option<ModuleSource>, CodaError>> ModuleLoader for F {
+    fn load(&self, path: &str) -> Result<Option<ModuleSource>, CodaError> {
+        self(path)
+    }
+}
+
+// tries each loader in the order they were added, stopping at the first one
+// that knows the path (or fails)
+#[derive(Default)]
+pub struct LoaderChain {
+    loaders: Vec<Box<dyn ModuleLoader>>,
+}
+
+impl LoaderChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, loader: impl ModuleLoader + 'static) -> Self {
+        self.loaders.push(Box::new(loader));
+        self
+    }
+}
+
+impl ModuleLoader for LoaderChain {
+    fn load(&self, path: &str) -> Result<Option<ModuleSource>, CodaError> {
+        for loader in &self.loaders {
+            if let Some(source) = loader.load(path)? {
+                return Ok(Some(source));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+// scripts kept in memory, keyed by the path the interpreter asks for. that's
+// the import path with relative ones joined onto the importing file's
+// directory, which for the entry script is the interpreter's `base_path`: with
+// an empty or `.` base path `"lib/util.coda"` answers `import "./lib/util.coda"`,
+// with `scripts` as the base path it has to be `"scripts/lib/util.coda"`
+#[derive(Default)]
+pub struct MemoryLoader {
+    pub files: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, path: impl Into<String>, source: impl Into<String>) -> Self {
+        self.files.insert(path.into(), source.into());
+        self
+    }
+}
+
+impl ModuleLoader for MemoryLoader {
+    fn load(&self, path: &str) -> Result<Option<ModuleSource>, CodaError> {
+        Ok(self.files.get(path).cloned().map(ModuleSource::Source))
+    }
+}
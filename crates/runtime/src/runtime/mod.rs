@@ -1,3 +1,7 @@
 pub mod ast;
+pub mod compiler;
 pub mod interpreter;
+pub mod loader;
+pub mod resolver;
 pub mod value;
+pub mod vm;
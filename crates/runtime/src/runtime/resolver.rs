@@ -0,0 +1,516 @@
+use crate::{env::Env, error::CodaError, runtime::ast::*, utils::StackGuard};
+use std::collections::{HashMap, HashSet};
+
+// binds every variable in `statements` to a frame slot or to the module env,
+// reporting undeclared variables and duplicate declarations along the way.
+// `env` is the module env the statements will run in; names already defined
+// there (std modules, earlier repl input) count as declared, as do the names
+// the module's imports bind, with `imported` holding what each plain `import`
+// brings in. `stack` bounds how deep the statements can nest. returns the
+// number of slots the top-level frame needs
+pub fn resolve(statements: &mut [Stmt], env: &Env, imported: &ImportedNames, stack: StackGuard) -> Result<usize, CodaError> {
+    let mut resolver = Resolver {
+        env,
+        stack,
+        globals: HashSet::new(),
+        constants: HashSet::new(),
+        imports: HashSet::new(),
+        imported,
+        unchecked: false,
+        functions: vec![FunctionScope::default()],
+    };
+
+    resolver.declare_globals(statements)?;
+    resolver.statements(statements)?;
+
+    Ok(resolver.functions.pop().map_or(0, |f| f.locals))
+}
+
+// the names a plain `import` binds, by import path, and whether each is const
+pub type ImportedNames = HashMap<String, HashMap<String, bool>>;
+
+#[derive(Default)]
+struct FunctionScope {
+    scopes: Vec<HashMap<String, usize>>,
+    locals: usize,
+    // slots bound with `const`
+    constants: HashSet<usize>,
+    // loops enclosing the current statement, for `break` and `continue`
+    loops: usize,
+    // `finally` blocks enclosing it, which control flow can't jump out of
+    finally: usize,
+}
+
+struct Resolver<'a> {
+    env: &'a Env,
+    stack: StackGuard,
+    globals: HashSet<String>,
+    // module-level names bound with `const` by these statements
+    constants: HashSet<String>,
+    // names bound by imports
+    imports: HashSet<String>,
+    imported: &'a ImportedNames,
+    // a plain `import` of a module that couldn't be read ahead fails when it
+    // runs, so names nothing else declares are left to the runtime until then
+    unchecked: bool,
+    functions: Vec<FunctionScope>,
+}
+
+impl Resolver<'_> {
+    // module-level declarations are visible everywhere in the module, even
+    // before the statement that declares them, so functions can call each other
+    fn declare_globals(&mut self, statements: &[Stmt]) -> Result<(), CodaError> {
+        for stmt in statements {
+            let (name, is_const) = match &stmt.kind {
+                StmtKind::Let { name, is_const, .. } => (name, *is_const),
+                StmtKind::Function { name, .. } => (name, false),
+
+                StmtKind::Import(import) => {
+                    self.declare_import(import);
+                    continue;
+                }
+
+                _ => continue,
+            };
+
+            if !self.globals.insert(name.clone()) {
+                return Err(CodaError::resolve(format!("`{name}` is already declared")).at(stmt.span));
+            }
+
+            if is_const {
+                self.constants.insert(name.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    // imports run in source order along with everything else, so only the
+    // names they bind are declared here
+    fn declare_import(&mut self, import: &Import) {
+        match &import.names {
+            ImportNames::All => match self.imported.get(&import.path) {
+                Some(names) => {
+                    for (name, is_const) in names {
+                        self.imports.insert(name.clone());
+
+                        if *is_const {
+                            self.constants.insert(name.clone());
+                        }
+                    }
+                }
+
+                None => self.unchecked = true,
+            },
+
+            ImportNames::Namespace(alias) => {
+                self.imports.insert(alias.clone());
+                self.constants.insert(alias.clone());
+            }
+
+            ImportNames::Only(names) => self.imports.extend(names.iter().map(|(_, alias)| alias.clone())),
+        }
+    }
+
+    fn statements(&mut self, statements: &mut [Stmt]) -> Result<(), CodaError> {
+        for stmt in statements {
+            self.statement(stmt)?;
+        }
+
+        Ok(())
+    }
+
+    fn block(&mut self, statements: &mut [Stmt]) -> Result<(), CodaError> {
+        self.begin_scope();
+        self.hoist_functions(statements)?;
+        self.statements(statements)?;
+        self.end_scope();
+
+        Ok(())
+    }
+
+    // local functions are declared up front for the same reason as globals
+    fn hoist_functions(&mut self, statements: &mut [Stmt]) -> Result<(), CodaError> {
+        for stmt in statements {
+            if let StmtKind::Function { name, slot, .. } = &mut stmt.kind {
+                *slot = self.declare(name).map_err(|err| err.at(stmt.span))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &mut Stmt) -> Result<(), CodaError> {
+        let span = stmt.span;
+
+        if self.stack.exceeded() {
+            return Err(CodaError::resolve("code is nested too deeply").at(span));
+        }
+
+        self.statement_kind(&mut stmt.kind).map_err(|err| err.at(span))
+    }
+
+    fn statement_kind(&mut self, stmt: &mut StmtKind) -> Result<(), CodaError> {
+        match stmt {
+            StmtKind::Let {
+                name,
+                slot,
+                value,
+                is_const,
+                is_exported,
+                ..
+            } => {
+                self.expression(value)?;
+                self.check_export(name, *is_exported)?;
+
+                if !self.is_module_scope() {
+                    *slot = self.declare(name)?;
+
+                    if let (Slot::Local { index, .. }, true) = (*slot, *is_const) {
+                        self.current().constants.insert(index);
+                    }
+                }
+
+                Ok(())
+            }
+
+            StmtKind::Function {
+                name,
+                params,
+                body,
+                locals,
+                is_exported,
+                ..
+            } => {
+                self.check_export(name, *is_exported)?;
+
+                *locals = self.function(params, body)?;
+
+                Ok(())
+            }
+
+            StmtKind::Return(_) if self.current().finally > 0 => Err(CodaError::resolve("`return` inside a `finally` block")),
+
+            StmtKind::Return(expr) => match expr {
+                Some(e) => self.expression(e),
+                None => Ok(()),
+            },
+
+            StmtKind::If { condition, then_branch, else_branch } => {
+                self.expression(condition)?;
+                self.block(then_branch)?;
+
+                match else_branch {
+                    Some(else_branch) => self.block(else_branch),
+                    None => Ok(()),
+                }
+            }
+
+            StmtKind::While { condition, body } => {
+                self.expression(condition)?;
+                self.loop_body(body)
+            }
+
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                // the initializer's bindings belong to the loop, not the enclosing block
+                self.begin_scope();
+
+                if let Some(initializer) = initializer {
+                    self.statement(initializer)?;
+                }
+
+                if let Some(condition) = condition {
+                    self.expression(condition)?;
+                }
+
+                if let Some(increment) = increment {
+                    self.expression(increment)?;
+                }
+
+                self.loop_body(body)?;
+                self.end_scope();
+
+                Ok(())
+            }
+
+            StmtKind::ForIn { name, slot, iterable, body } => {
+                self.expression(iterable)?;
+
+                self.begin_scope();
+                *slot = self.declare(name)?;
+                self.loop_body(body)?;
+                self.end_scope();
+
+                Ok(())
+            }
+
+            StmtKind::Break | StmtKind::Continue if self.current().loops == 0 => {
+                let keyword = if matches!(stmt, StmtKind::Break) { "break" } else { "continue" };
+
+                match self.current().finally {
+                    0 => Err(CodaError::resolve(format!("`{keyword}` outside of a loop"))),
+                    _ => Err(CodaError::resolve(format!("`{keyword}` inside a `finally` block"))),
+                }
+            }
+
+            StmtKind::Break | StmtKind::Continue => Ok(()),
+
+            StmtKind::Try { body, catch, finally } => {
+                self.block(body)?;
+
+                if let Some(catch) = catch {
+                    self.begin_scope();
+                    catch.slot = self.declare(&catch.name)?;
+                    self.block(&mut catch.body)?;
+                    self.end_scope();
+                }
+
+                if let Some(finally) = finally {
+                    // loops outside the block can't be broken out of from inside it
+                    let loops = std::mem::take(&mut self.current().loops);
+
+                    self.current().finally += 1;
+
+                    let result = self.block(finally);
+
+                    self.current().finally -= 1;
+                    self.current().loops = loops;
+
+                    result?;
+                }
+
+                Ok(())
+            }
+
+            StmtKind::Throw(expr) => self.expression(expr),
+
+            StmtKind::Block(statements) => self.block(statements),
+
+            StmtKind::Import(import) => {
+                if self.is_module_scope() {
+                    Ok(())
+                } else {
+                    Err(CodaError::resolve(format!("`import {}` is only allowed at the top level of a module", import.path)))
+                }
+            }
+
+            StmtKind::Expr(expr) => self.expression(expr),
+        }
+    }
+
+    fn expression(&mut self, expr: &mut Expr) -> Result<(), CodaError> {
+        let span = expr.span;
+
+        if self.stack.exceeded() {
+            return Err(CodaError::resolve("code is nested too deeply").at(span));
+        }
+
+        self.expression_kind(&mut expr.kind).map_err(|err| err.at(span))
+    }
+
+    fn expression_kind(&mut self, expr: &mut ExprKind) -> Result<(), CodaError> {
+        match expr {
+            ExprKind::Literal(_) => Ok(()),
+
+            ExprKind::Variable { name, slot } => {
+                *slot = self.lookup(name)?;
+
+                Ok(())
+            }
+
+            ExprKind::Unary { operand, .. } => self.expression(operand),
+
+            ExprKind::Binary { left, right, .. } | ExprKind::Logical { left, right, .. } => {
+                self.expression(left)?;
+                self.expression(right)
+            }
+
+            ExprKind::Call { callee, args } => {
+                self.expression(callee)?;
+
+                for arg in args {
+                    self.expression(arg)?;
+                }
+
+                Ok(())
+            }
+
+            ExprKind::Assign { name, slot, value } => {
+                self.expression(value)?;
+
+                *slot = self.lookup(name)?;
+
+                if self.is_const(name, *slot) {
+                    return Err(CodaError::resolve(format!("cannot assign to constant `{name}`")));
+                }
+
+                Ok(())
+            }
+
+            ExprKind::Index { target, index } => {
+                self.expression(target)?;
+                self.expression(index)
+            }
+
+            ExprKind::IndexAssign { target, index, value, .. } => {
+                self.expression(target)?;
+                self.expression(index)?;
+                self.expression(value)
+            }
+
+            ExprKind::Slice { target, start, end } => {
+                self.expression(target)?;
+
+                for bound in [start, end].into_iter().flatten() {
+                    self.expression(bound)?;
+                }
+
+                Ok(())
+            }
+
+            ExprKind::Array(elements) => {
+                for el in elements {
+                    self.expression(el)?;
+                }
+
+                Ok(())
+            }
+
+            ExprKind::Interpolate(parts) => {
+                for part in parts {
+                    self.expression(part)?;
+                }
+
+                Ok(())
+            }
+
+            ExprKind::Map(entries) => {
+                for (_, value) in entries {
+                    self.expression(value)?;
+                }
+
+                Ok(())
+            }
+
+            ExprKind::Function { params, body, locals, .. } => {
+                *locals = self.function(params, body)?;
+
+                Ok(())
+            }
+        }
+    }
+
+    fn loop_body(&mut self, body: &mut [Stmt]) -> Result<(), CodaError> {
+        self.current().loops += 1;
+
+        let result = self.block(body);
+
+        self.current().loops -= 1;
+
+        result
+    }
+
+    // parameters take the first slots of the new frame, in order
+    // a default can see the params before it. the function's scope is popped
+    // even on error, so the loops around it are counted against their own
+    fn function(&mut self, params: &mut [Param], body: &mut [Stmt]) -> Result<usize, CodaError> {
+        self.functions.push(FunctionScope::default());
+        self.begin_scope();
+
+        let result = self.function_body(params, body);
+        let locals = self.functions.pop().map_or(0, |f| f.locals);
+
+        result.map(|_| locals)
+    }
+
+    fn function_body(&mut self, params: &mut [Param], body: &mut [Stmt]) -> Result<(), CodaError> {
+        for param in params {
+            if let Some(default) = &mut param.default {
+                self.expression(default)?;
+            }
+
+            self.declare(&param.name)?;
+        }
+
+        self.hoist_functions(body)?;
+        self.statements(body)
+    }
+
+    fn declare(&mut self, name: &str) -> Result<Slot, CodaError> {
+        let function = self.current();
+        let scope = function.scopes.last_mut().expect("declared a local outside of a block");
+
+        if scope.contains_key(name) {
+            return Err(CodaError::resolve(format!("`{name}` is already declared in this scope")));
+        }
+
+        // slots are never reused, since a closure may still hold on to one
+        let index = function.locals;
+
+        function.locals += 1;
+        scope.insert(name.to_string(), index);
+
+        Ok(Slot::Local { depth: 0, index })
+    }
+
+    fn lookup(&self, name: &str) -> Result<Slot, CodaError> {
+        for (depth, function) in self.functions.iter().rev().enumerate() {
+            for scope in function.scopes.iter().rev() {
+                if let Some(&index) = scope.get(name) {
+                    return Ok(Slot::Local { depth, index });
+                }
+            }
+        }
+
+        if self.globals.contains(name) || self.imports.contains(name) || self.env.has(name) || self.unchecked {
+            Ok(Slot::Global)
+        } else {
+            Err(CodaError::resolve(format!("undefined variable `{name}`")))
+        }
+    }
+
+    fn is_const(&self, name: &str, slot: Slot) -> bool {
+        match slot {
+            Slot::Local { depth, index } => self.functions[self.functions.len() - 1 - depth].constants.contains(&index),
+            Slot::Global if self.globals.contains(name) || self.imports.contains(name) => self.constants.contains(name),
+            Slot::Global => self.env.is_const(name),
+        }
+    }
+
+    #[inline(always)]
+    fn current(&mut self) -> &mut FunctionScope {
+        self.functions.last_mut().expect("resolver has no function scope")
+    }
+
+    fn check_export(&self, name: &str, is_exported: bool) -> Result<(), CodaError> {
+        if is_exported && !self.is_module_scope() {
+            Err(CodaError::resolve(format!("cannot export `{name}` outside the top level of a module")))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline(always)]
+    fn is_module_scope(&self) -> bool {
+        self.functions.len() == 1 && self.functions[0].scopes.is_empty()
+    }
+
+    #[inline(always)]
+    fn begin_scope(&mut self) {
+        if let Some(function) = self.functions.last_mut() {
+            function.scopes.push(HashMap::new());
+        }
+    }
+
+    #[inline(always)]
+    fn end_scope(&mut self) {
+        if let Some(function) = self.functions.last_mut() {
+            function.scopes.pop();
+        }
+    }
+}
@@ -1,23 +1,63 @@
-use crate::{runtime::{ast::Stmt, interpreter::Interpreter}, env::Env};
-use std::{cell::RefCell, rc::Rc};
+use crate::{
+    env::{Env, Frame},
+    error::CodaError,
+    frontend::token::TokenKind,
+    runtime::{
+        ast::{Param, Stmt},
+        compiler::Proto,
+        interpreter::Interpreter,
+    },
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{self, Write},
+    rc::Rc,
+};
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub enum Value {
+    Int(i64),
+    // a float
     Number(f64),
     String(String),
     Bool(bool),
     Null,
-    Array(Vec<Value>),
+    // shared, so changes through one binding show up through every alias
+    Array(Rc<RefCell<Vec<Value>>>),
+    // shared like arrays
+    Map(Rc<RefCell<Map>>),
+    // `start..end`, end exclusive
+    Range(i64, i64),
 
-    NativeFunction(fn(Vec<Value>) -> Value),
+    NativeFunction(Rc<NativeFunction>),
     Function(Function),
 }
 
 impl Value {
+    #[inline(always)]
+    pub fn array(items: Vec<Value>) -> Self {
+        Value::Array(Rc::new(RefCell::new(items)))
+    }
+
+    #[inline(always)]
+    pub fn map(map: Map) -> Self {
+        Value::Map(Rc::new(RefCell::new(map)))
+    }
+
+    pub fn native(name: &str, arity: Arity, func: impl Fn(&mut Interpreter, Vec<Value>) -> Result<Value, CodaError> + 'static) -> Self {
+        Value::NativeFunction(Rc::new(NativeFunction {
+            name: name.into(),
+            arity,
+            func: Box::new(func),
+        }))
+    }
+
     pub fn as_bool(&self) -> bool {
         match self {
             Value::Bool(b) => *b,
             Value::Null => false,
+            Value::Int(n) => *n != 0,
             Value::Number(n) => *n != 0.0,
             Value::String(s) => !s.is_empty(),
             _ => true,
@@ -25,38 +65,566 @@ impl Value {
     }
 }
 
-#[derive(Clone, Debug)]
+// string keys to values, iterated in the order keys were first inserted so
+// scripts behave the same on every run
+#[derive(Clone, Default)]
+pub struct Map {
+    entries: Vec<(String, Value)>,
+    indices: HashMap<String, usize>,
+}
+
+impl Map {
+    #[inline(always)]
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.indices.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    // replacing a value keeps the key where it was
+    pub fn insert(&mut self, key: String, value: Value) {
+        match self.indices.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline(always)]
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// how values look when printed or interpolated into a string. strings
+// inside arrays and maps are shown without quotes, like at the top level
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{n}"),
+            // whole floats keep a `.0` so they can be told apart from ints
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e16 => write!(f, "{n:.1}"),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::String(s) => f.write_str(s),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Null => f.write_str("null"),
+
+            // while an array or map is being printed it's borrowed, so finding
+            // it borrowed again means it contains itself
+            Value::Array(items) if items.try_borrow_mut().is_err() => f.write_str("[...]"),
+            Value::Map(map) if map.try_borrow_mut().is_err() => f.write_str("{...}"),
+
+            Value::Array(items) => {
+                f.write_str("[")?;
+
+                for (i, item) in items.borrow().iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+
+                    write!(f, "{item}")?;
+                }
+
+                f.write_str("]")
+            }
+
+            Value::Map(map) => {
+                f.write_str("{")?;
+
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+
+                    write!(f, "{key}: {value}")?;
+                }
+
+                f.write_str("}")
+            }
+
+            Value::Range(start, end) => write!(f, "{start}..{end}"),
+            Value::Function(_) | Value::NativeFunction(_) => f.write_str("<fn>"),
+        }
+    }
+}
+
+// derived, except that arrays don't show the `RefCell` they live in
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => f.debug_tuple("Int").field(n).finish(),
+            Value::Number(n) => f.debug_tuple("Number").field(n).finish(),
+            Value::String(s) => f.debug_tuple("String").field(s).finish(),
+            Value::Bool(b) => f.debug_tuple("Bool").field(b).finish(),
+            Value::Null => f.write_str("Null"),
+            Value::Array(items) if items.try_borrow_mut().is_err() => f.write_str("Array([...])"),
+            Value::Map(map) if map.try_borrow_mut().is_err() => f.write_str("Map({...})"),
+            Value::Array(items) => f.debug_tuple("Array").field(&items.borrow()).finish(),
+            Value::Map(map) => f.debug_tuple("Map").field(&map.borrow()).finish(),
+            Value::Range(start, end) => f.debug_tuple("Range").field(start).field(end).finish(),
+            Value::NativeFunction(func) => func.fmt(f),
+            Value::Function(func) => f.debug_tuple("Function").field(func).finish(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Plus,
+    Minus,
+    Star,
+    StarStar,
+    Slash,
+    Percent,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    EqualEqual,
+    BangEqual,
+    DotDot,
+}
+
+impl BinaryOp {
+    pub fn from_token(kind: &TokenKind) -> Option<Self> {
+        Some(match kind {
+            TokenKind::Plus | TokenKind::PlusEqual => BinaryOp::Plus,
+            TokenKind::Minus | TokenKind::MinusEqual => BinaryOp::Minus,
+            TokenKind::Star | TokenKind::StarEqual => BinaryOp::Star,
+            TokenKind::StarStar => BinaryOp::StarStar,
+            TokenKind::Slash | TokenKind::SlashEqual => BinaryOp::Slash,
+            TokenKind::Percent => BinaryOp::Percent,
+            TokenKind::Greater => BinaryOp::Greater,
+            TokenKind::GreaterEqual => BinaryOp::GreaterEqual,
+            TokenKind::Less => BinaryOp::Less,
+            TokenKind::LessEqual => BinaryOp::LessEqual,
+            TokenKind::EqualEqual => BinaryOp::EqualEqual,
+            TokenKind::BangEqual => BinaryOp::BangEqual,
+            TokenKind::DotDot => BinaryOp::DotDot,
+
+            _ => return None,
+        })
+    }
+}
+
+// shown as written in source, for error messages
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Star => "*",
+            BinaryOp::StarStar => "**",
+            BinaryOp::Slash => "/",
+            BinaryOp::Percent => "%",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::EqualEqual => "==",
+            BinaryOp::BangEqual => "!=",
+            BinaryOp::DotDot => "..",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Minus,
+    Bang,
+}
+
+impl UnaryOp {
+    pub fn from_token(kind: &TokenKind) -> Option<Self> {
+        Some(match kind {
+            TokenKind::Minus => UnaryOp::Minus,
+            TokenKind::Bang => UnaryOp::Bang,
+
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UnaryOp::Minus => "-",
+            UnaryOp::Bang => "!",
+        })
+    }
+}
+
+pub fn unary(operator: UnaryOp, v: &Value) -> Result<Value, CodaError> {
+    match (operator, v) {
+        (UnaryOp::Minus, Value::Int(n)) => n.checked_neg().map(Value::Int).ok_or_else(|| CodaError::runtime(format!("integer overflow: -({n})"))),
+        (UnaryOp::Minus, Value::Number(n)) => Ok(Value::Number(-n)),
+        (UnaryOp::Bang, v) => Ok(Value::Bool(!v.as_bool())),
+
+        _ => Err(CodaError::runtime(format!("unsupported operation: {operator}{v:?}"))),
+    }
+}
+
+// shared by the tree-walker and the vm so both backends agree on semantics
+pub fn binary(l: &Value, operator: BinaryOp, r: &Value) -> Result<Value, CodaError> {
+    match (l, r, operator) {
+        // two ints stay ints; an int mixed with a float becomes a float
+        (Value::Int(a), Value::Int(b), _) => int_binary(*a, operator, *b),
+        (Value::Int(a), Value::Number(b), _) => float_binary(*a as f64, operator, *b).ok_or_else(|| unsupported(l, operator, r)),
+        (Value::Number(a), Value::Int(b), _) => float_binary(*a, operator, *b as f64).ok_or_else(|| unsupported(l, operator, r)),
+        (Value::Number(a), Value::Number(b), _) => float_binary(*a, operator, *b).ok_or_else(|| unsupported(l, operator, r)),
+
+        // strings
+        (Value::String(a), Value::String(b), BinaryOp::Plus) => {
+            let mut s = String::with_capacity(a.len() + b.len());
+
+            s.push_str(a);
+            s.push_str(b);
+
+            Ok(Value::String(s))
+        }
+
+        (Value::String(a), b @ (Value::Int(_) | Value::Number(_)), BinaryOp::Plus) => {
+            let mut s = String::with_capacity(a.len() + 16);
+
+            s.push_str(a);
+            write!(&mut s, "{b}").unwrap();
+
+            Ok(Value::String(s))
+        }
+
+        (a @ (Value::Int(_) | Value::Number(_)), Value::String(b), BinaryOp::Plus) => {
+            let mut s = String::with_capacity(b.len() + 16);
+
+            write!(&mut s, "{a}").unwrap();
+            s.push_str(b);
+
+            Ok(Value::String(s))
+        }
+
+        (Value::String(a), Value::String(b), BinaryOp::EqualEqual) => Ok(Value::Bool(a == b)),
+        (Value::String(a), Value::String(b), BinaryOp::BangEqual) => Ok(Value::Bool(a != b)),
+
+        _ => Err(unsupported(l, operator, r)),
+    }
+}
+
+// arithmetic is overflow-checked. `/` and `%` round towards negative
+// infinity, so `a == (a / b) * b + a % b` and `%` takes the sign of `b`
+fn int_binary(a: i64, operator: BinaryOp, b: i64) -> Result<Value, CodaError> {
+    let overflow = || CodaError::runtime(format!("integer overflow: {a} {operator} {b}"));
+
+    Ok(match operator {
+        BinaryOp::Plus => Value::Int(a.checked_add(b).ok_or_else(overflow)?),
+        BinaryOp::Minus => Value::Int(a.checked_sub(b).ok_or_else(overflow)?),
+        BinaryOp::Star => Value::Int(a.checked_mul(b).ok_or_else(overflow)?),
+
+        BinaryOp::Slash | BinaryOp::Percent if b == 0 => return Err(CodaError::runtime("division by zero")),
+
+        BinaryOp::Slash => {
+            let q = a.checked_div(b).ok_or_else(overflow)?;
+
+            Value::Int(if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q })
+        }
+
+        BinaryOp::Percent => {
+            let r = a.wrapping_rem(b);
+
+            Value::Int(if r != 0 && (r < 0) != (b < 0) { r + b } else { r })
+        }
+
+        // a negative exponent can't give an int back
+        BinaryOp::StarStar if b < 0 => Value::Number((a as f64).powf(b as f64)),
+        BinaryOp::StarStar => Value::Int(u32::try_from(b).ok().and_then(|b| a.checked_pow(b)).ok_or_else(overflow)?),
+
+        BinaryOp::Greater => Value::Bool(a > b),
+        BinaryOp::GreaterEqual => Value::Bool(a >= b),
+        BinaryOp::Less => Value::Bool(a < b),
+        BinaryOp::LessEqual => Value::Bool(a <= b),
+        BinaryOp::EqualEqual => Value::Bool(a == b),
+        BinaryOp::BangEqual => Value::Bool(a != b),
+
+        BinaryOp::DotDot => Value::Range(a, b),
+    })
+}
+
+// `None` for operators floats don't support
+fn float_binary(a: f64, operator: BinaryOp, b: f64) -> Option<Value> {
+    Some(match operator {
+        BinaryOp::Plus => Value::Number(a + b),
+        BinaryOp::Minus => Value::Number(a - b),
+        BinaryOp::Star => Value::Number(a * b),
+        BinaryOp::Slash => Value::Number(a / b),
+        // floored like the int version
+        BinaryOp::Percent => {
+            let r = a % b;
+
+            Value::Number(if r != 0.0 && (r < 0.0) != (b < 0.0) { r + b } else { r })
+        }
+        BinaryOp::StarStar => Value::Number(a.powf(b)),
+
+        BinaryOp::Greater => Value::Bool(a > b),
+        BinaryOp::GreaterEqual => Value::Bool(a >= b),
+        BinaryOp::Less => Value::Bool(a < b),
+        BinaryOp::LessEqual => Value::Bool(a <= b),
+        BinaryOp::EqualEqual => Value::Bool(a == b),
+        BinaryOp::BangEqual => Value::Bool(a != b),
+
+        BinaryOp::DotDot => return None,
+    })
+}
+
+#[inline(always)]
+fn unsupported(l: &Value, operator: BinaryOp, r: &Value) -> CodaError {
+    CodaError::runtime(format!("unsupported operation: {l:?} {operator} {r:?}"))
+}
+
+pub fn index(target: &Value, index: &Value) -> Result<Value, CodaError> {
+    match target {
+        Value::Array(items) => {
+            let items = items.borrow();
+
+            Ok(items[position(index, items.len(), false)?].clone())
+        }
+
+        Value::String(s) => {
+            let i = position(index, s.chars().count(), false)?;
+
+            Ok(Value::String(s.chars().nth(i).map(String::from).unwrap_or_default()))
+        }
+
+        // missing keys read as null
+        Value::Map(map) => Ok(map.borrow().get(key(index)?).cloned().unwrap_or(Value::Null)),
+
+        v => Err(CodaError::runtime(format!("cannot index into {v:?}"))),
+    }
+}
+
+pub fn set_index(target: &Value, index: &Value, value: Value) -> Result<(), CodaError> {
+    match target {
+        Value::Array(items) => {
+            let mut items = items.borrow_mut();
+            let i = position(index, items.len(), false)?;
+
+            items[i] = value;
+
+            Ok(())
+        }
+
+        Value::Map(map) => {
+            map.borrow_mut().insert(key(index)?.to_string(), value);
+
+            Ok(())
+        }
+
+        Value::String(_) => Err(CodaError::runtime("strings can't be changed in place")),
+
+        v => Err(CodaError::runtime(format!("cannot index into {v:?}"))),
+    }
+}
+
+// `start` and `end` are null for an open bound. always copies
+pub fn slice(target: &Value, start: &Value, end: &Value) -> Result<Value, CodaError> {
+    let len = match target {
+        Value::Array(items) => items.borrow().len(),
+        Value::String(s) => s.chars().count(),
+
+        v => return Err(CodaError::runtime(format!("cannot slice {v:?}"))),
+    };
+
+    let start = if let Value::Null = start { 0 } else { position(start, len, true)? };
+    let end = if let Value::Null = end { len } else { position(end, len, true)? };
+
+    if start > end {
+        return Err(CodaError::runtime(format!("slice starts at {start} but ends at {end}")));
+    }
+
+    match target {
+        Value::Array(items) => Ok(Value::array(items.borrow()[start..end].to_vec())),
+        Value::String(s) => Ok(Value::String(s.chars().skip(start).take(end - start).collect())),
+
+        _ => unreachable!(),
+    }
+}
+
+#[inline(always)]
+fn key(index: &Value) -> Result<&str, CodaError> {
+    match index {
+        Value::String(key) => Ok(key),
+
+        v => Err(CodaError::runtime(format!("map keys must be strings, got {v:?}"))),
+    }
+}
+
+// turns `index` into an offset into something `len` long, counting negative
+// indices back from the end. slice bounds may also sit one past the end
+fn position(index: &Value, len: usize, is_bound: bool) -> Result<usize, CodaError> {
+    let n = match index {
+        Value::Int(n) => *n,
+
+        v => return Err(CodaError::runtime(format!("index must be an integer, got {v:?}"))),
+    };
+
+    let i = if n < 0 { n + len as i64 } else { n };
+    let limit = if is_bound { len as i64 + 1 } else { len as i64 };
+
+    if (0..limit).contains(&i) {
+        Ok(i as usize)
+    } else {
+        Err(CodaError::runtime(format!("index {n} out of range for length {len}")))
+    }
+}
+
+// one step of a `for x in` loop. `cursor` starts at 0 and is whatever the
+// previous step returned; it's a plain number so the vm can keep it on its stack
+pub fn iterate(iterable: &Value, cursor: usize) -> Result<Option<(Value, usize)>, CodaError> {
+    match iterable {
+        Value::Array(items) => Ok(items.borrow().get(cursor).map(|item| (item.clone(), cursor + 1))),
+
+        // strings step a char at a time; the cursor is a byte offset
+        Value::String(s) => Ok(s[cursor..].chars().next().map(|c| (Value::String(c.to_string()), cursor + c.len_utf8()))),
+
+        // maps step through their keys
+        Value::Map(map) => Ok(map.borrow().entries.get(cursor).map(|(key, _)| (Value::String(key.clone()), cursor + 1))),
+
+        Value::Range(start, end) => {
+            let n = start.saturating_add(cursor as i64);
+
+            Ok((n < *end).then_some((Value::Int(n), cursor + 1)))
+        }
+
+        v => Err(CodaError::runtime(format!("cannot iterate over {v:?}"))),
+    }
+}
+
+#[derive(Clone)]
 pub struct Function {
-    pub name: String,
-    pub params: Vec<String>,
-    pub body: Vec<Stmt>,
+    pub name: Rc<str>,
+    // the file it was defined in, for tracebacks
+    pub file: Option<Rc<str>>,
+    pub arity: Arity,
+    pub body: FunctionBody,
+    pub locals: usize,
     pub closure: Rc<RefCell<Env>>,
+    pub frame: Rc<Frame>,
 }
 
-pub struct CodaFunction {
-    pub params: Vec<String>,
-    pub body: Vec<Stmt>,
-    pub closure: Rc<RefCell<Env>>,
+pub type NativeFn = dyn Fn(&mut Interpreter, Vec<Value>) -> Result<Value, CodaError>;
+
+// a function written in rust. it's handed the interpreter so it can call
+// back into script functions, and can fail like any other call. the arity is
+// checked before it runs
+pub struct NativeFunction {
+    pub name: Rc<str>,
+    pub arity: Arity,
+    pub func: Box<NativeFn>,
 }
 
-impl CodaFunction {
-    pub fn call(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> Value {
-        let new_env = Rc::new(RefCell::new(Env::new_with_parent(Some(self.closure.clone()))));
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction").field("name", &self.name).field("arity", &self.arity).finish_non_exhaustive()
+    }
+}
+
+// leaves out the closure, whose env usually holds the function itself
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function").field("name", &self.name).field("arity", &self.arity).finish_non_exhaustive()
+    }
+}
+
+// a function is either walked from its ast or run from compiled bytecode,
+// depending on which backend created it
+#[derive(Clone, Debug)]
+pub enum FunctionBody {
+    Ast { params: Rc<[Param]>, body: Rc<[Stmt]> },
+    Bytecode(Rc<Proto>),
+}
+
+// how many arguments a function takes: `required` ones, then `optional` ones
+// that have defaults, then any number more if it has a rest parameter
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Arity {
+    pub required: usize,
+    pub optional: usize,
+    pub rest: bool,
+}
+
+impl Arity {
+    #[inline(always)]
+    pub const fn exactly(count: usize) -> Self {
+        Self {
+            required: count,
+            optional: 0,
+            rest: false,
+        }
+    }
 
-        for (param, arg) in self.params.iter().zip(args) {
-            new_env.borrow_mut().define(param.clone(), arg);
+    #[inline(always)]
+    pub const fn at_least(count: usize) -> Self {
+        Self {
+            required: count,
+            optional: 0,
+            rest: true,
         }
+    }
 
-        let previous = interpreter.env.clone();
+    // the parser keeps defaulted params after required ones and the rest param last
+    pub fn of(params: &[Param]) -> Self {
+        Self {
+            required: params.iter().filter(|p| p.default.is_none() && !p.is_rest).count(),
+            optional: params.iter().filter(|p| p.default.is_some()).count(),
+            rest: params.last().is_some_and(|p| p.is_rest),
+        }
+    }
 
-        interpreter.env = new_env;
+    pub fn check(&self, name: &str, argc: usize) -> Result<(), CodaError> {
+        let max = self.required + self.optional;
 
-        for stmt in &self.body {
-            let _ = interpreter.execute(stmt.clone());
+        if argc >= self.required && (self.rest || argc <= max) {
+            return Ok(());
         }
 
-        interpreter.env = previous;
+        let name = if name.is_empty() { "<anonymous>" } else { name };
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+
+        let expected = if self.rest {
+            format!("at least {} argument{}", self.required, plural(self.required))
+        } else if self.optional > 0 {
+            format!("{} to {max} arguments", self.required)
+        } else {
+            format!("{max} argument{}", plural(max))
+        };
+
+        Err(CodaError::runtime(format!("`{name}` expects {expected} but got {argc}")))
+    }
+
+    // moves `args` into the first slots of `frame`. extras go into the rest
+    // param's array; params left out keep their slots for the defaults
+    pub fn bind(&self, frame: &Frame, mut args: Vec<Value>) {
+        let max = self.required + self.optional;
+        let mut slots = frame.slots.borrow_mut();
+
+        if self.rest {
+            slots[max] = Value::array(args.split_off(max.min(args.len())));
+        }
 
-        Value::Null
+        slots.iter_mut().zip(args).for_each(|(slot, arg)| *slot = arg);
     }
 }
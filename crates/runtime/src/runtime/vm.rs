@@ -0,0 +1,472 @@
+use crate::{
+    env::{Env, Frame},
+    error::CodaError,
+    runtime::{
+        compiler::{Op, Proto},
+        interpreter::Interpreter,
+        value::{self, Function, FunctionBody, Map, Value, binary, iterate, set_index, slice, unary},
+    },
+};
+use std::{cell::RefCell, fmt::Write, rc::Rc};
+
+struct CallFrame {
+    proto: Rc<Proto>,
+    ip: usize,
+    // stack index of the callee; the frame's temporaries live above it
+    base: usize,
+    // how many arguments the call passed, for `JumpIfArg`
+    argc: usize,
+    // the env and frame that were active before this frame was entered
+    caller_env: Rc<RefCell<Env>>,
+    caller_frame: Rc<Frame>,
+    // whether it has an entry on the interpreter's call stack. the script's
+    // frame shares the one `Interpreter::run` pushed
+    traced: bool,
+}
+
+// an open `try`, pushed by `Op::Try` or `Op::TryFinally`. the lengths are
+// what to unwind back to when an error reaches it
+struct Handler {
+    target: usize,
+    frames: usize,
+    stack: usize,
+    pending: usize,
+    finally: bool,
+}
+
+pub struct Vm<'a> {
+    interpreter: &'a mut Interpreter,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    handlers: Vec<Handler>,
+    // errors waiting for a `finally` block to finish before they're rethrown
+    pending: Vec<CodaError>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Self {
+            interpreter,
+            stack: Vec::with_capacity(256),
+            frames: Vec::new(),
+            handlers: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    // runs a top-level chunk in the interpreter's current env and frame
+    pub fn run(&mut self, proto: Rc<Proto>) -> Result<Value, CodaError> {
+        self.stack.push(Value::Null);
+        self.frames.push(CallFrame {
+            proto,
+            ip: 0,
+            base: 0,
+            argc: 0,
+            caller_env: self.interpreter.env.clone(),
+            caller_frame: self.interpreter.frame.clone(),
+            traced: false,
+        });
+
+        self.execute()
+    }
+
+    // calls a compiled function from outside the vm, e.g. from the tree-walker
+    pub fn call(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, CodaError> {
+        let argc = args.len();
+
+        self.stack.push(callee);
+        self.stack.extend(args);
+
+        if self.call_value(argc)? { self.execute() } else { Ok(self.stack.pop().unwrap_or(Value::Null)) }
+    }
+
+    fn execute(&mut self) -> Result<Value, CodaError> {
+        loop {
+            let err = match self.dispatch() {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+
+            let err = match self.frames.last() {
+                Some(frame) => err.at(frame.proto.chunk.spans[frame.ip - 1]),
+                None => err,
+            };
+
+            let err = self.interpreter.traced(err);
+
+            match self.handlers.pop() {
+                Some(handler) => self.catch(handler, err),
+
+                // an error leaves frames behind; dropping them puts the caller's scope back
+                None => {
+                    self.unwind(0);
+
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    // resumes at `handler` with `err`, dropping whatever was pushed since
+    // the try was entered
+    fn catch(&mut self, handler: Handler, err: CodaError) {
+        self.unwind(handler.frames);
+        self.stack.truncate(handler.stack);
+        self.pending.truncate(handler.pending);
+
+        if handler.finally {
+            self.pending.push(err);
+        } else {
+            self.stack.push(err.to_value());
+        }
+
+        if let Some(frame) = self.frames.last_mut() {
+            frame.ip = handler.target;
+        }
+    }
+
+    // pops frames down to `depth`, leaving the scope of the last one popped
+    fn unwind(&mut self, depth: usize) {
+        while self.frames.len() > depth {
+            let frame = self.frames.pop().unwrap();
+
+            if frame.traced {
+                self.interpreter.call_stack.pop();
+            }
+
+            self.interpreter.env = frame.caller_env;
+            self.interpreter.frame = frame.caller_frame;
+        }
+    }
+
+    fn dispatch(&mut self) -> Result<Value, CodaError> {
+        loop {
+            let frame = self.frames.last_mut().expect("vm has no active frame");
+            let op = frame.proto.chunk.code[frame.ip];
+
+            frame.ip += 1;
+
+            match op {
+                Op::Constant(index) => {
+                    let value = self.frame().proto.chunk.constants[index as usize].clone();
+
+                    self.stack.push(value);
+                }
+
+                Op::Pop => {
+                    self.pop();
+                }
+
+                Op::DefineGlobal(index, is_const) => {
+                    let name = self.name(index);
+                    let value = self.pop();
+
+                    self.interpreter.env.borrow_mut().declare(name, value, is_const, false);
+                }
+
+                Op::DefineExport(index, is_const) => {
+                    let name = self.name(index);
+                    let value = self.pop();
+
+                    self.interpreter.env.borrow_mut().declare(name, value, is_const, true);
+                }
+
+                Op::GetGlobal(index) => {
+                    let name = &self.frame().proto.chunk.names[index as usize];
+                    let value = self.interpreter.env.borrow().get(name).ok_or_else(|| CodaError::runtime(format!("undefined variable `{name}`")))?;
+
+                    self.stack.push(value);
+                }
+
+                Op::SetGlobal(index) => {
+                    let name = self.name(index);
+                    let value = self.pop();
+
+                    self.interpreter.env.borrow_mut().assign(&name, value)?;
+                    self.stack.push(Value::Null);
+                }
+
+                Op::DefineLocal(index) => {
+                    let value = self.pop();
+
+                    self.interpreter.frame.set(0, index as usize, value);
+                }
+
+                Op::GetLocal(depth, index) => {
+                    let value = self.interpreter.frame.get(depth as usize, index as usize);
+
+                    self.stack.push(value);
+                }
+
+                Op::SetLocal(depth, index) => {
+                    let value = self.pop();
+
+                    self.interpreter.frame.set(depth as usize, index as usize, value);
+                    self.stack.push(Value::Null);
+                }
+
+                Op::Unary(operator) => {
+                    let v = self.pop();
+
+                    self.stack.push(unary(operator, &v)?);
+                }
+
+                Op::Index => {
+                    let index = self.pop();
+                    let target = self.pop();
+
+                    self.stack.push(value::index(&target, &index)?);
+                }
+
+                Op::SetIndex(operator) => {
+                    let mut value = self.pop();
+                    let index = self.pop();
+                    let target = self.pop();
+
+                    if let Some(operator) = operator {
+                        value = binary(&value::index(&target, &index)?, operator, &value)?;
+                    }
+
+                    set_index(&target, &index, value)?;
+                    self.stack.push(Value::Null);
+                }
+
+                Op::Slice => {
+                    let end = self.pop();
+                    let start = self.pop();
+                    let target = self.pop();
+
+                    self.stack.push(slice(&target, &start, &end)?);
+                }
+
+                Op::Binary(operator) => {
+                    let r = self.pop();
+                    let l = self.pop();
+
+                    self.stack.push(binary(&l, operator, &r)?);
+                }
+
+                Op::Jump(target) => {
+                    self.frames.last_mut().unwrap().ip = target as usize;
+                }
+
+                Op::JumpIfFalse(target) => {
+                    if !self.pop().as_bool() {
+                        self.frames.last_mut().unwrap().ip = target as usize;
+                    }
+                }
+
+                Op::JumpIfFalseOrPop(target) => {
+                    if self.peek().as_bool() {
+                        self.pop();
+                    } else {
+                        self.frames.last_mut().unwrap().ip = target as usize;
+                    }
+                }
+
+                Op::JumpIfTrueOrPop(target) => {
+                    if self.peek().as_bool() {
+                        self.frames.last_mut().unwrap().ip = target as usize;
+                    } else {
+                        self.pop();
+                    }
+                }
+
+                Op::JumpIfArg(index, target) => {
+                    let frame = self.frames.last_mut().unwrap();
+
+                    if (index as usize) < frame.argc {
+                        frame.ip = target as usize;
+                    }
+                }
+
+                Op::IterNext(target) => {
+                    let len = self.stack.len();
+                    let Value::Int(cursor) = self.stack[len - 1] else {
+                        unreachable!("for-in cursor is always a number");
+                    };
+
+                    match iterate(&self.stack[len - 2], cursor as usize)? {
+                        Some((item, next)) => {
+                            self.stack[len - 1] = Value::Int(next as i64);
+                            self.stack.push(item);
+                        }
+
+                        None => {
+                            self.stack.truncate(len - 2);
+                            self.frames.last_mut().unwrap().ip = target as usize;
+                        }
+                    }
+                }
+
+                Op::Call(argc) => {
+                    self.call_value(argc as usize)?;
+                }
+
+                Op::Try(target) | Op::TryFinally(target) => {
+                    self.handlers.push(Handler {
+                        target: target as usize,
+                        frames: self.frames.len(),
+                        stack: self.stack.len(),
+                        pending: self.pending.len(),
+                        finally: matches!(op, Op::TryFinally(_)),
+                    });
+                }
+
+                Op::EndTry => {
+                    self.handlers.pop();
+                }
+
+                Op::Throw => {
+                    let value = self.pop();
+
+                    return Err(CodaError::thrown(value));
+                }
+
+                Op::Rethrow => {
+                    return Err(self.pending.pop().expect("rethrow without a pending error"));
+                }
+
+                Op::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().unwrap();
+
+                    if frame.traced {
+                        self.interpreter.call_stack.pop();
+                    }
+
+                    self.stack.truncate(frame.base);
+                    self.interpreter.env = frame.caller_env;
+                    self.interpreter.frame = frame.caller_frame;
+
+                    if self.frames.is_empty() {
+                        return Ok(value);
+                    }
+
+                    self.stack.push(value);
+                }
+
+                Op::Array(len) => {
+                    let start = self.stack.len() - len as usize;
+                    let values = self.stack.split_off(start);
+
+                    self.stack.push(Value::array(values));
+                }
+
+                Op::Interpolate(len) => {
+                    let start = self.stack.len() - len as usize;
+                    let mut s = String::new();
+
+                    for part in self.stack.drain(start..) {
+                        write!(s, "{part}").unwrap();
+                    }
+
+                    self.stack.push(Value::String(s));
+                }
+
+                Op::Map(len) => {
+                    let start = self.stack.len() - 2 * len as usize;
+                    let mut entries = self.stack.split_off(start).into_iter();
+                    let mut map = Map::default();
+
+                    while let (Some(Value::String(key)), Some(value)) = (entries.next(), entries.next()) {
+                        map.insert(key, value);
+                    }
+
+                    self.stack.push(Value::map(map));
+                }
+
+                Op::Closure(index) => {
+                    let proto = self.frame().proto.chunk.protos[index as usize].clone();
+
+                    self.stack.push(Value::Function(Function {
+                        name: proto.name.clone(),
+                        file: self.interpreter.file.clone(),
+                        arity: proto.arity,
+                        locals: proto.locals,
+                        body: FunctionBody::Bytecode(proto),
+                        closure: self.interpreter.env.clone(),
+                        frame: self.interpreter.frame.clone(),
+                    }));
+                }
+
+                Op::Import(index) => {
+                    let import = self.frame().proto.chunk.imports[index as usize].clone();
+
+                    self.set_line();
+                    self.interpreter.execute_import(&import)?;
+                }
+            }
+        }
+    }
+
+    // returns true when a new frame was pushed, false when the result is
+    // already on the stack
+    fn call_value(&mut self, argc: usize) -> Result<bool, CodaError> {
+        let base = self.stack.len() - argc - 1;
+        let args = self.stack.split_off(base + 1);
+        let callee = self.pop();
+
+        self.set_line();
+
+        if let Value::Function(func) = &callee
+            && let FunctionBody::Bytecode(proto) = &func.body
+        {
+            proto.arity.check(&proto.name, argc)?;
+            self.interpreter.push_frame(func.name.clone(), func.file.clone(), false)?;
+
+            let frame = Frame::new(proto.locals, Some(func.frame.clone()));
+
+            proto.arity.bind(&frame, args);
+
+            self.stack.push(callee.clone());
+            self.frames.push(CallFrame {
+                proto: proto.clone(),
+                ip: 0,
+                base,
+                argc,
+                caller_env: std::mem::replace(&mut self.interpreter.env, func.closure.clone()),
+                caller_frame: std::mem::replace(&mut self.interpreter.frame, frame),
+                traced: true,
+            });
+
+            return Ok(true);
+        }
+
+        let result = self.interpreter.call(callee, args)?;
+
+        self.stack.push(result);
+
+        Ok(false)
+    }
+
+    // records the current line on the call stack, before calling out
+    #[inline(always)]
+    fn set_line(&mut self) {
+        if let Some(frame) = self.frames.last() {
+            let line = frame.proto.chunk.spans[frame.ip - 1].line;
+
+            self.interpreter.set_line(line);
+        }
+    }
+
+    #[inline(always)]
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("vm has no active frame")
+    }
+
+    #[inline(always)]
+    fn name(&self, index: u32) -> String {
+        self.frame().proto.chunk.names[index as usize].clone()
+    }
+
+    #[inline(always)]
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("vm stack underflow")
+    }
+
+    #[inline(always)]
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("vm stack underflow")
+    }
+}
@@ -1,19 +1,67 @@
+// identifiers follow unicode's XID_Start/XID_Continue, plus a leading `_`
 #[inline(always)]
-pub fn is_alpha(c: u8) -> bool {
-    c.is_ascii_alphabetic() || c == b'_'
+pub fn is_ident_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
 }
 
 #[inline(always)]
-pub fn is_alphanumeric(c: u8) -> bool {
-    is_alpha(c) || c.is_ascii_digit()
+pub fn is_ident_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
 }
 
 #[inline(always)]
-pub fn slice_to_string(slice: &[u8]) -> String {
-    std::str::from_utf8(slice).unwrap_or("").to_string()
+pub fn trim_quotes(s: &str) -> &str {
+    s.strip_prefix('"').unwrap_or(s).strip_suffix('"').unwrap_or(s)
+}
+
+// drops `.` and folds `..` into the component before it, without touching the
+// filesystem, so `lib/./a/../b.coda` reads as `lib/b.coda` in errors
+pub fn normalize_path(path: &std::path::Path) -> std::path::PathBuf {
+    use std::path::Component;
+
+    let mut out = std::path::PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(out.components().next_back(), Some(Component::Normal(_))) => {
+                out.pop();
+            }
+            Component::ParentDir if matches!(out.components().next_back(), Some(Component::RootDir | Component::Prefix(_))) => {}
+            other => out.push(other),
+        }
+    }
+
+    out
+}
+
+// how much stack the recursive passes (parser, resolver, compiler, tree
+// walker) may use before failing with an error. a spawned thread gets 2 MiB by
+// default, so this leaves room for the host's own frames below them
+pub const DEFAULT_STACK_LIMIT: usize = 1024 * 1024;
+
+// bounds how far a recursive pass can grow the stack from where it started,
+// so deeply nested or recursive scripts fail instead of overflowing it
+#[derive(Clone, Copy, Debug)]
+pub struct StackGuard {
+    base: usize,
+    limit: usize,
+}
+
+impl StackGuard {
+    pub fn new(limit: usize) -> Self {
+        Self { base: stack_position(), limit }
+    }
+
+    #[inline(always)]
+    pub fn exceeded(&self) -> bool {
+        self.base.abs_diff(stack_position()) > self.limit
+    }
 }
 
 #[inline(always)]
-pub fn trim_quotes(s: &str) -> &str {
-    s.strip_prefix('"').unwrap_or(s).strip_suffix('"').unwrap_or(s)
+fn stack_position() -> usize {
+    let marker = 0u8;
+
+    std::hint::black_box(&marker) as *const u8 as usize
 }
@@ -0,0 +1,52 @@
+// the vm and the tree walker must agree on everything a script can observe:
+// what it prints, the error it stops with and the traceback that comes with it
+
+mod common;
+
+use common::{run, run_with};
+
+#[test]
+fn example_script_matches() {
+    let examples = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../examples");
+    let source = std::fs::read_to_string(examples.join("test.coda")).unwrap();
+    let run = run_with(&source, &[], |interpreter| interpreter.base_path = examples.clone());
+
+    assert_eq!(run.error, None);
+    assert!(run.output.starts_with("4.0\ntest export from test2\n"), "{}", run.output);
+}
+
+#[test]
+fn feature_snippets_match() {
+    let snippets = [
+        "print(1 + 2 * 3, 7 / 2, 7 % 3, 2 ** 10, -(3), !true, 1 < 2 && 2 < 1 || \"x\")",
+        "print(0x1f, 0b101, 0o17, 1_000, 1e3, 2.5e-1)",
+        "let s = \"a\\tb\\n\\u{e9}\" print(s, r\"raw\\n\", \"${1 + 1} and ${\"x\"}\")",
+        "let a = [1, 2, 3, 4] a[0] = 9 a[1] += 1 print(a, a[-1], a[1:3], a[:2], a[2:], \"coda\"[1:3])",
+        "let m = { a: 1, \"b c\": [2] } m.a += 1 m[\"d\"] = null print(m, m.a, m[\"b c\"][0])",
+        "for let i = 0; i < 5; i += 1 { if i == 1 { continue } if i == 4 { break } print(i) }",
+        "for x in [1, 2] { print(x) } for c in \"hi\" { print(c) } for i in 0..3 { print(i) }",
+        "let i = 0 while true { i += 1 if i > 2 { break } } print(i)",
+        "fn f(a, b = a * 2, ...rest) { return [a, b, rest] } print(f(1), f(1, 2), f(1, 2, 3, 4))",
+        "fn counter() { let n = 0 return fn() { n += 1 return n } } let c = counter() c() print(c())",
+        "fn fib(n) { if n < 2 { return n } return fib(n - 1) + fib(n - 2) } print(fib(15))",
+        "try { throw { message: \"boom\", code: 1 } } catch (e) { print(e.message, e.code, e.kind) } finally { print(\"done\") }",
+        "fn f() { for x in [1, 2] { try { return x } finally { print(\"left\", x) } } } print(f())",
+        "try { [1][5] } catch (e) { print(e.kind, e.message) }",
+        "print(9223372036854775807 + 1)",
+        "const x = 1 x = 2",
+        "print(undefined)",
+        "fn f(a) {} f()",
+        "fn f(n) { return f(n + 1) } f(0)",
+        "let x = 1 x()",
+        "fn a() { throw \"inner\" } fn b() { a() } b()",
+        "let m = {} print(m.missing)",
+        "print(\"a\" - 1)",
+        "import std.math as m print(m.sqrt(16), m.pi > 3)",
+        "import { sqrt as root } from std.math print(root(9))",
+        "import std.nope",
+    ];
+
+    for snippet in snippets {
+        run(snippet);
+    }
+}
@@ -0,0 +1,82 @@
+// arrays, strings and maps: indexing, slicing and changing them in place
+
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn indexes_count_from_either_end() {
+    assert_eq!(output("let a = [10, 20, 30]\nprint(a[0], a[2], a[-1], a[-3])"), "10 30 30 10\n");
+    assert_eq!(output("let s = \"héllo\"\nprint(s[1], s[-1])"), "é o\n");
+}
+
+#[test]
+fn slices_are_half_open_and_copy() {
+    let source = r#"
+let a = [10, 20, 30, 40]
+print(a[1:3], a[-2:], a[:-1], a[2:2])
+let b = a[:]
+b[0] = 0
+print(a[0], b[0])
+print("héllo"[1:3], "héllo"[5:])
+"#;
+
+    assert_eq!(output(source), "[20, 30] [30, 40] [10, 20, 30] []\n10 0\nél \n");
+}
+
+#[test]
+fn arrays_are_shared_and_changed_in_place() {
+    let source = r#"
+let a = [1, 2, 3]
+let b = a
+b[0] = 99
+a[1] *= 10
+a[-1] += 1
+print(a, b)
+"#;
+
+    assert_eq!(output(source), "[99, 20, 4] [99, 20, 4]\n");
+}
+
+#[test]
+fn bad_indexes_are_errors() {
+    assert_eq!(error("let a = [1]\na[1]"), "2:1: index 1 out of range for length 1");
+    assert_eq!(error("let a = [1]\na[-2]"), "2:1: index -2 out of range for length 1");
+    assert_eq!(error("let a = [1]\na[0.5]"), "2:1: index must be an integer, got Number(0.5)");
+    assert_eq!(error("let a = [1, 2, 3, 4]\na[3:1]"), "2:1: slice starts at 3 but ends at 1");
+    assert_eq!(error("let a = [1]\na[0:5]"), "2:1: index 5 out of range for length 1");
+    assert_eq!(error("let s = \"ab\"\ns[0] = \"x\""), "2:1: strings can't be changed in place");
+    assert_eq!(error("let x = 5\nx[0]"), "2:1: cannot index into Int(5)");
+}
+
+#[test]
+fn map_literals_keep_insertion_order() {
+    let source = r#"
+let m = { b: 1, "a key": 2, nested: { x: [1, 2] } }
+print(m, m.b, m["a key"], m.nested.x[1])
+for k in m { print(k) }
+"#;
+
+    assert_eq!(output(source), "{b: 1, a key: 2, nested: {x: [1, 2]}} 1 2 2\nb\na key\nnested\n");
+}
+
+#[test]
+fn map_fields_can_be_added_and_changed() {
+    let source = r#"
+let m = { count: 1 }
+let alias = m
+m.count += 1
+m.name = "coda"
+m["other"] = null
+print(alias, m.missing)
+"#;
+
+    assert_eq!(output(source), "{count: 2, name: coda, other: null} null\n");
+}
+
+#[test]
+fn bad_map_access_is_an_error() {
+    assert_eq!(error("let m = {}\nm.a.b = 1"), "2:1: cannot index into Null");
+    assert_eq!(error("let m = {}\nm[1]"), "2:1: map keys must be strings, got Int(1)");
+    assert_eq!(error("let x = 1\nx.field"), "2:1: cannot index into Int(1)");
+}
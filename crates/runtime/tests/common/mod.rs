@@ -0,0 +1,198 @@
+// shared by the integration tests: runs a script on both backends with a
+// `print` that writes into a buffer, and checks they agree on what it printed
+// and how it failed. each test file uses a different slice of this
+#![allow(dead_code)]
+
+use coda_runtime::{
+    env::Env,
+    error::CodaError,
+    frontend::{lexer, parser},
+    runtime::{
+        interpreter::{Backend, Interpreter},
+        loader::{LoaderChain, MemoryLoader, ModuleSource},
+        value::{Arity, Value},
+    },
+    utils::StackGuard,
+};
+use std::{
+    cell::RefCell,
+    fmt::Write,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// what a script printed, and the error it stopped with
+#[derive(Debug, PartialEq)]
+pub struct Run {
+    pub output: String,
+    pub error: Option<String>,
+    pub kind: Option<&'static str>,
+    pub traceback: Option<String>,
+}
+
+// `files` are modules the script can import, kept in memory
+pub fn run_on(backend: Backend, source: &str, files: &[(&str, &str)], setup: impl FnOnce(&mut Interpreter)) -> Run {
+    let output = Rc::new(RefCell::new(String::new()));
+    let mut env = Env::new();
+
+    register_print(&mut env, &output);
+
+    let files = files.iter().fold(MemoryLoader::new(), |loader, (path, source)| loader.with(*path, *source));
+    let loader = LoaderChain::new().with(stub_std(output.clone())).with(files);
+    let mut interpreter = Interpreter::new(env, std::path::PathBuf::new(), Some(Box::new(loader)));
+
+    interpreter.backend = backend;
+
+    setup(&mut interpreter);
+
+    let stack = StackGuard::new(interpreter.stack_limit);
+    let result = lexer::scan_bytes(source.as_bytes())
+        .and_then(|tokens| match parser::parse_within(tokens, stack) {
+            (ast, errors) if errors.is_empty() => Ok(ast),
+            (_, errors) => Err(errors.into_iter().next().unwrap()),
+        })
+        .and_then(|ast| interpreter.run(ast));
+    let output = output.borrow().clone();
+
+    match result {
+        Ok(()) => Run {
+            output,
+            error: None,
+            kind: None,
+            traceback: None,
+        },
+        Err(err) => Run {
+            output,
+            error: Some(err.to_string()),
+            kind: Some(err.kind()),
+            traceback: err.traceback(),
+        },
+    }
+}
+
+// the tree walker recurses on the host stack, so scripts get a thread with as
+// much of it as the cli gives them. `run_on` alone runs on the caller's thread
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+const STACK_LIMIT: usize = STACK_SIZE - 8 * 1024 * 1024;
+
+// runs `source` on both backends, failing the test if they disagree
+pub fn run_with(source: &str, files: &[(&str, &str)], setup: impl Fn(&mut Interpreter) + Sync) -> Run {
+    let setup = |interpreter: &mut Interpreter| {
+        interpreter.stack_limit = STACK_LIMIT;
+        setup(interpreter);
+    };
+
+    let (tree, vm) = std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || (run_on(Backend::Tree, source, files, setup), run_on(Backend::Vm, source, files, setup)));
+
+        thread.unwrap().join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    });
+
+    assert_eq!(tree, vm, "the backends disagree on:\n{source}");
+
+    vm
+}
+
+pub fn run(source: &str) -> Run {
+    run_with(source, &[], |_| {})
+}
+
+pub fn run_files(source: &str, files: &[(&str, &str)]) -> Run {
+    run_with(source, files, |_| {})
+}
+
+// what `source` printed, failing the test if it errored
+pub fn output(source: &str) -> String {
+    let run = run(source);
+
+    assert_eq!(run.error, None, "unexpected error from:\n{source}");
+
+    run.output
+}
+
+// the error `source` stopped with, failing the test if it didn't
+pub fn error(source: &str) -> String {
+    run(source).error.unwrap_or_else(|| panic!("expected an error from:\n{source}"))
+}
+
+fn register_print(env: &mut Env, output: &Rc<RefCell<String>>) {
+    let output = output.clone();
+
+    env.define(
+        "print".to_string(),
+        Value::native("print", Arity::at_least(0), move |_, args| {
+            let line: Vec<_> = args.iter().map(ToString::to_string).collect();
+
+            writeln!(output.borrow_mut(), "{}", line.join(" ")).unwrap();
+
+            Ok(Value::Null)
+        }),
+    );
+}
+
+// just enough of the standard library for the examples, without depending on
+// coda-std (which depends on this crate)
+fn stub_std(output: Rc<RefCell<String>>) -> impl Fn(&str) -> Result<Option<ModuleSource>, CodaError> {
+    move |path| {
+        let output = output.clone();
+
+        Ok(match path {
+            "std.io" => Some(ModuleSource::Native(Box::new(move |env: &mut Env| register_print(env, &output)))),
+            "std.math" => Some(ModuleSource::Native(Box::new(|env: &mut Env| {
+                env.define_const("pi".to_string(), Value::Number(std::f64::consts::PI));
+                env.define_const("e".to_string(), Value::Number(std::f64::consts::E));
+                env.define(
+                    "sqrt".to_string(),
+                    Value::native("sqrt", Arity::exactly(1), |_, args| match &args[0] {
+                        Value::Int(n) => Ok(Value::Number((*n as f64).sqrt())),
+                        Value::Number(n) => Ok(Value::Number(n.sqrt())),
+                        other => Err(CodaError::native(format!("`sqrt` expects a number, got {other:?}"))),
+                    }),
+                );
+            }))),
+
+            _ => None,
+        })
+    }
+}
+
+// files on disk, for the imports a memory loader can't stand in for. the
+// directory is removed again when this is dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(files: &[(&str, &str)]) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!("coda-test-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for (path, source) in files {
+            let path = dir.join(path);
+
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+
+        Self(dir.canonicalize().unwrap())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    // `path` inside the directory, as a string for error messages
+    pub fn join(&self, path: &str) -> String {
+        self.0.join(path).display().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
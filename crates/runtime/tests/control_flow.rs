@@ -0,0 +1,68 @@
+// branches and loops, including `break` and `continue` out of nested ones
+
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn if_and_else() {
+    let source = r#"
+fn sign(n) { if n < 0 { return "-" } else { if n == 0 { return "0" } } return "+" }
+print(sign(-2), sign(0), sign(5))
+"#;
+
+    assert_eq!(output(source), "- 0 +\n");
+}
+
+#[test]
+fn c_style_for_loops() {
+    assert_eq!(output("let total = 0\nfor let i = 1; i <= 4; i += 1 { total += i }\nprint(total)"), "10\n");
+    assert_eq!(output("let n = 0\nfor ; n < 3; { n += 1 }\nprint(n)"), "3\n");
+}
+
+#[test]
+fn for_in_walks_arrays_strings_ranges_and_map_keys() {
+    let source = r#"
+for x in [1, 2] { print(x) }
+for c in "hé" { print(c) }
+for i in 3..5 { print(i) }
+for k in { a: 1, b: 2 } { print(k) }
+"#;
+
+    assert_eq!(output(source), "1\n2\nh\né\n3\n4\na\nb\n");
+}
+
+#[test]
+fn break_and_continue_affect_the_innermost_loop() {
+    let source = r#"
+for let i = 0; i < 3; i += 1 {
+    let j = 0
+    while true {
+        j += 1
+        if j == 2 { continue }
+        if j > 3 { break }
+        print(i, j)
+    }
+    if i == 0 { continue }
+    print("after", i)
+}
+"#;
+
+    assert_eq!(output(source), "0 1\n0 3\n1 1\n1 3\nafter 1\n2 1\n2 3\nafter 2\n");
+}
+
+#[test]
+fn returning_from_inside_a_loop() {
+    assert_eq!(
+        output("fn find(xs, x) { for y in xs { if y == x { return true } } return false }\nprint(find([1, 2], 2), find([1], 3))"),
+        "true false\n"
+    );
+}
+
+#[test]
+fn misplaced_loop_control_is_an_error() {
+    assert_eq!(error("break"), "1:1: `break` outside of a loop");
+    assert_eq!(error("while true { fn f() { continue } }"), "1:23: `continue` outside of a loop");
+    assert_eq!(error("while true { let g = fn() { break } }"), "1:29: `break` outside of a loop");
+    assert_eq!(error("for x in 5 {}"), "1:10: cannot iterate over Int(5)");
+}
@@ -0,0 +1,46 @@
+// every stage fails with a `CodaError` whose variant names the stage and
+// whose span points at the offending source
+
+mod common;
+
+use coda_runtime::{error::CodaError, frontend::lexer};
+use common::run;
+
+#[test]
+fn each_stage_reports_its_own_kind() {
+    let cases = [
+        ("let s = \"unterminated", "lex"),
+        ("let = 1", "parse"),
+        ("print(nope)", "resolve"),
+        ("let a = [1] a[3]", "runtime"),
+        ("import \"./missing.coda\"", "import"),
+        ("import std.math sqrt(\"x\")", "native"),
+        ("throw 1", "error"),
+    ];
+
+    for (source, kind) in cases {
+        assert_eq!(run(source).kind, Some(kind), "{source}");
+    }
+}
+
+#[test]
+fn errors_point_at_the_failing_expression() {
+    assert_eq!(run("let a = 1\nlet b = a + [2]").error.as_deref(), Some("2:9: unsupported operation: Int(1) + Array([Int(2)])"));
+    assert_eq!(run("let x = 1\n  x()").error.as_deref(), Some("2:3: can only call functions, got Int(1)"));
+}
+
+#[test]
+fn render_underlines_the_span() {
+    let err = lexer::scan("let a = 1 $ 2").unwrap_err();
+
+    assert!(matches!(err, CodaError::Lex(_)));
+    assert_eq!(err.render("let a = 1 $ 2"), "unexpected character `$`\n --> <input>:1:11\n  |\n1 | let a = 1 $ 2\n  |           ^");
+}
+
+#[test]
+fn display_includes_file_and_note() {
+    let err = CodaError::runtime("boom").in_file("main.coda").with_note("a note");
+
+    assert_eq!(err.to_string(), "main.coda: boom (a note)");
+    assert_eq!(err.in_file("other.coda").info().file.as_deref(), Some("main.coda"));
+}
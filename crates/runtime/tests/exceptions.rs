@@ -0,0 +1,82 @@
+// `throw`, `try`, `catch` and `finally`: anything thrown or raised by the
+// runtime can be caught as a map, and `finally` runs however its block is left
+
+mod common;
+
+use common::{error, output, run};
+
+#[test]
+fn thrown_values_are_caught_as_maps() {
+    assert_eq!(output("try { throw \"boom\" } catch (e) { print(e.message, e.kind) }"), "boom error\n");
+    assert_eq!(output("try { throw { code: 5 } } catch (e) { print(e.message, e.code, e.kind) }"), "{code: 5} 5 error\n");
+    assert_eq!(output("try { throw { message: \"bad\", kind: \"mine\" } } catch (e) { print(e.message, e.kind) }"), "bad mine\n");
+}
+
+#[test]
+fn uncaught_throws_use_the_message_field() {
+    assert_eq!(error("throw { message: \"bad\", code: 1 }"), "1:1: bad");
+    assert_eq!(error("throw { code: 5 }"), "1:1: {code: 5}");
+    assert_eq!(error("throw [1, 2]"), "1:1: [1, 2]");
+}
+
+#[test]
+fn runtime_and_native_errors_are_catchable() {
+    assert_eq!(output("try { let x = [][0] } catch (e) { print(e.kind, e.message) }"), "runtime index 0 out of range for length 0\n");
+    assert_eq!(output("import std.math\ntry { sqrt(\"x\") } catch (e) { print(e.kind) }"), "native\n");
+}
+
+// catching a map copies it rather than filling it in, so throwing it again
+// gets a trace of its own
+#[test]
+fn the_thrown_map_is_left_alone() {
+    let source = r#"
+let m = { message: "x" }
+fn f() { throw m }
+try { f() } catch (e) { print(e.trace[-1].function) }
+print(m)
+try { throw m } catch (e) { print(e.trace[-1].function) }
+"#;
+
+    assert_eq!(output(source), "f\n{message: x}\n<script>\n");
+}
+
+#[test]
+fn rethrowing_keeps_the_message_and_kind() {
+    assert_eq!(output("try { try { throw \"a\" } catch (e) { throw e } } catch (e) { print(e.message, e.kind) }"), "a error\n");
+    assert_eq!(output("try { try { [][0] } catch (e) { throw e } } catch (e) { print(e.kind) }"), "runtime\n");
+}
+
+#[test]
+fn finally_runs_however_the_block_is_left() {
+    let source = r#"
+for i in 0..3 {
+    try {
+        if i == 1 { continue }
+        if i == 2 { break }
+        print(i)
+    } finally {
+        print("finally", i)
+    }
+}
+fn f() { try { return "returned" } finally { print("finally f") } }
+print(f())
+try { try { [][0] } finally { print("finally inner") } } catch (e) { print("caught", e.kind) }
+"#;
+
+    assert_eq!(output(source), "0\nfinally 0\nfinally 1\nfinally 2\nfinally f\nreturned\nfinally inner\ncaught runtime\n");
+
+    let run = run("try { throw 1 } catch (e) { throw 2 } finally { print(\"finally\") }");
+
+    assert_eq!((run.output.as_str(), run.error.as_deref()), ("finally\n", Some("1:29: 2")));
+}
+
+#[test]
+fn finally_cannot_jump_out() {
+    assert_eq!(error("fn f() { try {} finally { return 1 } }"), "1:27: `return` inside a `finally` block");
+    assert_eq!(error("while true { try {} finally { break } }"), "1:31: `break` inside a `finally` block");
+}
+
+#[test]
+fn the_caught_error_is_scoped_to_catch() {
+    assert_eq!(error("try {} catch (e) {} print(e)"), "1:27: undefined variable `e`");
+}
@@ -0,0 +1,42 @@
+// calls check their argument count, fill in defaults and collect the rest
+
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn defaults_fill_in_missing_arguments() {
+    let source = r#"
+let g = fn(x, y = x + 1) { return y }
+fn f(a = loud()) { return a }
+fn loud() { print("default evaluated") return 7 }
+print(g(1), g(1, 5))
+print(f(1))
+print(f())
+"#;
+
+    assert_eq!(output(source), "2 5\n1\ndefault evaluated\n7\n");
+}
+
+#[test]
+fn the_rest_parameter_collects_extra_arguments() {
+    assert_eq!(
+        output("fn f(a, b = 2, ...rest) { return [a, b, rest] }\nprint(f(1), f(1, 3), f(1, 3, 4, 5))"),
+        "[1, 2, []] [1, 3, []] [1, 3, [4, 5]]\n"
+    );
+}
+
+#[test]
+fn argument_counts_are_checked() {
+    assert_eq!(error("fn f(a, b) {}\nf(1, 2, 3)"), "2:1: `f` expects 2 arguments but got 3");
+    assert_eq!(error("fn f(a, b = 1) {}\nf()"), "2:1: `f` expects 1 to 2 arguments but got 0");
+    assert_eq!(error("fn f(a, ...rest) {}\nf()"), "2:1: `f` expects at least 1 argument but got 0");
+}
+
+#[test]
+fn bad_parameter_lists_are_errors() {
+    assert_eq!(error("fn f(a = 1, b) {}"), "1:13: parameter `b` needs a default value, since one before it has one");
+    assert_eq!(error("fn f(...rest, a) {}"), "1:15: the rest parameter must be the last one");
+    assert_eq!(error("fn f(...rest = 1) {}"), "1:14: the rest parameter can't have a default value");
+    assert_eq!(error("fn f(a, a) {}"), "1:1: `a` is already declared in this scope");
+}
@@ -0,0 +1,226 @@
+// throws generated and mutated programs at the whole pipeline, from the lexer
+// through the resolver to both backends, and checks that every one of them
+// comes back as a result instead of a panic. the generator is seeded, so a
+// failure reproduces on every run
+
+use coda_runtime::{
+    env::Env,
+    frontend::{lexer, parser, token::TokenKind},
+    runtime::{
+        interpreter::{Backend, Interpreter},
+        value::{Arity, Value},
+    },
+};
+use std::panic::{self, AssertUnwindSafe};
+
+const SEED: u64 = 0x5eed_c0da;
+const CASES: usize = 2000;
+
+// deep recursion is part of what's being tested, so give it the same room the
+// cli does
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+// loop-free, since a mutated loop may never finish
+const CORPUS: &str = r##"
+let count = 0
+count += 1
+count = count * 2 - 1 / 3 % 2 ** 3
+const name = "coda"
+
+/// greets someone
+fn greet(user, greeting = "hello", ...rest) {
+    return "${greeting}, ${user}! ${rest}"
+}
+
+fn fib(n) {
+    if n < 2 { return n }
+    return fib(n - 1) + fib(n - 2)
+}
+
+fn counter() {
+    let n = 0
+    return fn() { n += 1 return n }
+}
+
+let next = counter()
+next()
+let nums = [1, 2.5, [3, 4], -0x10, 1_000, 1e3]
+nums[0] = nums[-1]
+nums[2][1] += 1
+let part = nums[1:3]
+let m = { a: 1, "b c": [nums, null], d: { e: true } }
+m.a = m.d.e && !false || null
+m["f"] = r#"raw "str""# + name[1:] + name[0]
+print(greet(name), greet("x", "hi", 1, 2), fib(10), next(), part, m, 0..3)
+/* a /* nested */ comment */
+print(sqrt(16), testexport, 7 / -2, 7.0 / 2, "a" + 1)
+try {
+    apply(fn(x) { return apply(fib, x) }, nums)
+} catch (e) {
+    print(e.message, e.kind, e.trace[0].line)
+    throw { message: "again", code: 1 }
+} finally {
+    print(count)
+}
+"##;
+
+#[rustfmt::skip]
+const FRAGMENTS: &[&str] = &[
+    "let ", "const ", "fn ", "return ", "if ", "else ", "while ", "for ", " in ", "break ", "continue ", "import ", "export ",
+    "try ", "catch ", "(e)", "finally ", "throw ", " as ", " from ",
+    "x", "y", "f", "print", "apply", "greet", "count", "nums", "m",
+    "(", ")", "{", "}", "[", "]", ",", ":", ".", "..", "...", "=", "+=", "-=", "*=", "/=",
+    "+", "-", "*", "/", "%", "**", "!", "&&", "||", "==", "!=", "<", "<=", ">", ">=",
+    "0", "1", "-1", "2.5", "0x7fffffffffffffff", "9223372036854775807", "1e400", "0b102", "1_",
+    "\"s\"", "\"a${", "}\"", "\"\\u{110000}\"", "r#\"raw\"#", "r\"", "true", "false", "null",
+    "\"./missing.coda\"", "/*", "*/", "//", "///", "\n", " ", "é", "名前", "€", "\\", "\"", "$", ";", "\t",
+];
+
+// xorshift64*
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn fragments(rng: &mut Rng) -> Vec<u8> {
+    (0..1 + rng.below(60)).flat_map(|_| FRAGMENTS[rng.below(FRAGMENTS.len())].bytes()).collect()
+}
+
+// mostly bytes that mean something to the lexer, with the odd arbitrary one
+fn random_bytes(rng: &mut Rng) -> Vec<u8> {
+    const BYTES: &[u8] = b" \n(){}[]\"$.,:=+-*/%!<>&|_xyfn0123456789";
+
+    (0..rng.below(80)).map(|_| if rng.below(8) == 0 { rng.next() as u8 } else { BYTES[rng.below(BYTES.len())] }).collect()
+}
+
+// deletes, duplicates or splices in a few ranges of the corpus
+fn mutation(rng: &mut Rng) -> Vec<u8> {
+    let mut source = CORPUS.as_bytes().to_vec();
+
+    for _ in 0..1 + rng.below(4) {
+        let start = rng.below(source.len());
+        let end = (start + rng.below(24)).min(source.len());
+
+        match rng.below(3) {
+            0 => {
+                source.drain(start..end);
+            }
+
+            1 => {
+                let copy = source[start..end].to_vec();
+                let at = rng.below(source.len());
+
+                source.splice(at..at, copy);
+            }
+
+            _ => {
+                let fragment = FRAGMENTS[rng.below(FRAGMENTS.len())].bytes();
+
+                source.splice(start..end, fragment);
+            }
+        }
+    }
+
+    source
+}
+
+fn run(source: &[u8]) {
+    let Ok(tokens) = lexer::scan_bytes(source) else { return };
+
+    // loops in a mangled program may never finish, so those only get parsed
+    let terminates = !tokens.iter().any(|t| matches!(t.kind, TokenKind::While | TokenKind::For));
+
+    let _ = parser::parse_recovering(tokens.clone());
+
+    let Ok(ast) = parser::parse(tokens) else { return };
+
+    if !terminates {
+        return;
+    }
+
+    for backend in [Backend::Tree, Backend::Vm] {
+        let mut env = Env::new();
+
+        for name in ["print", "sqrt", "testexport"] {
+            env.define(name.to_string(), Value::native(name, Arity::at_least(0), |_, _| Ok(Value::Null)));
+        }
+
+        // calls back into the script, the way higher-order builtins do
+        env.define(
+            "apply".to_string(),
+            Value::native("apply", Arity::at_least(1), |interpreter, mut args| {
+                let callee = args.remove(0);
+
+                interpreter.call(callee, args)
+            }),
+        );
+
+        let mut interpreter = Interpreter::new(env, std::path::PathBuf::from("."), None);
+
+        interpreter.backend = backend;
+
+        let _ = interpreter.run(ast.clone());
+    }
+}
+
+fn check(source: &[u8]) {
+    if panic::catch_unwind(AssertUnwindSafe(|| run(source))).is_err() {
+        panic!("input panicked:\n{}", String::from_utf8_lossy(source));
+    }
+}
+
+#[test]
+fn no_input_panics() {
+    let thread = std::thread::Builder::new().stack_size(STACK_SIZE).spawn(|| {
+        let mut rng = Rng(SEED);
+
+        check(CORPUS.as_bytes());
+
+        for _ in 0..CASES {
+            check(&fragments(&mut rng));
+            check(&random_bytes(&mut rng));
+            check(&mutation(&mut rng));
+        }
+    });
+
+    thread.unwrap().join().unwrap();
+}
+
+// runs on the test harness's own thread, which is as small as the ones hosts
+// usually spawn, so the stack guards have to stop these before it overflows
+#[test]
+fn deep_input_fails_cleanly() {
+    let nested = |open: &str, close: &str| format!("{}1{}", open.repeat(250), close.repeat(250));
+
+    let cases = [
+        format!("let x = {}1{}", "(".repeat(100_000), ")".repeat(100_000)),
+        format!("let x = {}1", "-".repeat(100_000)),
+        format!("let x = 1{}", " + 1".repeat(100_000)),
+        format!("let x = 2{}", " ** 2".repeat(100_000)),
+        format!("let x = [1]{}", "[0]".repeat(100_000)),
+        format!("if true {}{}", "{ if true ".repeat(100_000), "}".repeat(100_000)),
+        format!("let s = {}", "\"${".repeat(100_000)),
+        format!("let x = {}", nested("(", ")")),
+        format!("let x = {}", nested("[", "]")),
+        format!("let x = {}", nested("{ a: ", " }")),
+        format!("let x = {}", nested("fn() { return ", " }")),
+        "fn f(n) { return f(n + 1) }\nf(0)".to_string(),
+        "let g = fn(n) { return 1 + g(n) }\ng(0)".to_string(),
+        "fn f(n) { if true { while true { for x in [1] { try { return f(n) } finally {} } } } }\nf(0)".to_string(),
+        "fn f(n) { return apply(f, n) }\nf(0)".to_string(),
+    ];
+
+    for case in cases {
+        check(case.as_bytes());
+    }
+}
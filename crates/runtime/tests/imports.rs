@@ -0,0 +1,65 @@
+// a file only hands out what it exports, either straight into the importer,
+// as a namespace map, or a few names at a time
+
+mod common;
+
+use common::run_files;
+
+const UTIL: &str = r#"
+import std.io
+export fn shout(s) { return s + "!" }
+export const version = 2
+fn helper() { return 1 }
+let hidden = 3
+print("util ran")
+"#;
+
+fn run(source: &str) -> common::Run {
+    run_files(source, &[("util.coda", UTIL), ("plain.coda", "fn a() { return 1 }\nlet b = 2")])
+}
+
+#[test]
+fn plain_imports_bind_every_export() {
+    assert_eq!(run("import \"./util.coda\"\nprint(shout(\"hi\"), version)").output, "util ran\nhi! 2\n");
+}
+
+#[test]
+fn private_bindings_stay_private() {
+    assert_eq!(run("import \"./util.coda\"\nprint(helper())").error.as_deref(), Some("2:7: undefined variable `helper`"));
+    assert_eq!(run("import \"./util.coda\"\nprint(hidden)").error.as_deref(), Some("2:7: undefined variable `hidden`"));
+    assert_eq!(run("import \"./util.coda\" as util\nprint(util)").output, "util ran\n{shout: <fn>, version: 2}\n");
+
+    // a file without any `export` hands out nothing
+    assert_eq!(run("import \"./plain.coda\"\nprint(b)").error.as_deref(), Some("2:7: undefined variable `b`"));
+}
+
+#[test]
+fn namespaces_bind_the_module_as_a_constant_map() {
+    assert_eq!(run("import \"./util.coda\" as util\nprint(util.shout(\"a\"), util.version)").output, "util ran\na! 2\n");
+    assert_eq!(run("import \"./util.coda\" as util\nutil = 1").error.as_deref(), Some("2:1: cannot assign to constant `util`"));
+}
+
+#[test]
+fn selective_imports_bind_only_the_names_asked_for() {
+    assert_eq!(run("import { shout, version as v } from \"./util.coda\"\nprint(shout(\"x\"), v)").output, "util ran\nx! 2\n");
+    assert_eq!(run("import { shout } from \"./util.coda\"\nprint(version)").error.as_deref(), Some("2:7: undefined variable `version`"));
+    assert_eq!(
+        run("import { helper } from \"./util.coda\"").error.as_deref(),
+        Some("1:1: module `./util.coda` has no export named `helper`")
+    );
+}
+
+#[test]
+fn const_exports_stay_const() {
+    assert_eq!(
+        run("import { version } from \"./util.coda\"\nversion = 3").error.as_deref(),
+        Some("2:1: cannot assign to constant `version`")
+    );
+}
+
+#[test]
+fn a_module_runs_once_however_often_it_is_imported() {
+    let run = run("import \"./util.coda\" as u\nimport { shout } from \"./util.coda\"\nimport \"./util.coda\"\nprint(u.version)");
+
+    assert_eq!(run.output, "util ran\n2\n");
+}
@@ -0,0 +1,90 @@
+// tokens carry where they came from, down to the byte, and the lexer copes
+// with everything a source file can throw at it
+
+mod common;
+
+use coda_runtime::{
+    frontend::{lexer, parser, token::Span},
+    runtime::ast::{ExprKind, StmtKind},
+};
+use common::output;
+
+fn parse(source: &str) -> Vec<StmtKind> {
+    parser::parse(lexer::scan(source).unwrap()).unwrap().into_iter().map(|stmt| stmt.kind).collect()
+}
+
+fn span(start: usize, end: usize, line: usize, column: usize) -> Span {
+    Span { start, end, line, column }
+}
+
+#[test]
+fn tokens_have_byte_offsets_and_char_columns() {
+    let tokens = lexer::scan("let é = \"ü\" +\n  12").unwrap();
+    let spans: Vec<_> = tokens.iter().map(|token| token.span).collect();
+
+    assert_eq!(
+        spans,
+        [
+            span(0, 3, 1, 1),
+            span(4, 6, 1, 5),
+            span(7, 8, 1, 7),
+            span(9, 13, 1, 9),
+            span(14, 15, 1, 13),
+            span(18, 20, 2, 3),
+            span(20, 20, 2, 5),
+        ]
+    );
+}
+
+#[test]
+fn ast_nodes_cover_their_source() {
+    let ast = parser::parse(lexer::scan("let a = 1\nprint(a + 2)").unwrap()).unwrap();
+
+    assert_eq!(ast[0].span, span(0, 9, 1, 1));
+    assert_eq!(ast[1].span, span(10, 22, 2, 1));
+
+    let StmtKind::Expr(call) = &ast[1].kind else { panic!("expected a call, got {:?}", ast[1].kind) };
+    let ExprKind::Call { args, .. } = &call.kind else {
+        panic!("expected a call, got {:?}", call.kind)
+    };
+
+    assert_eq!(args[0].span, span(16, 21, 2, 7));
+}
+
+#[test]
+fn block_comments_nest() {
+    let ast = parse("// line\nlet a = 1 /* b /* nested */ still */ + 2");
+
+    assert_eq!(ast.len(), 1);
+    assert_eq!(lexer::scan("/* open /* */").unwrap_err().to_string(), "1:1: unterminated block comment");
+}
+
+#[test]
+fn doc_comments_attach_to_the_next_declaration() {
+    let docs: Vec<_> = parse("/// first\n/// second\nexport fn f() {}\n/// a constant\nconst c = 1\nlet plain = 2")
+        .into_iter()
+        .map(|stmt| match stmt {
+            StmtKind::Function { doc, .. } | StmtKind::Let { doc, .. } => doc,
+            other => panic!("expected a declaration, got {other:?}"),
+        })
+        .collect();
+
+    assert_eq!(docs, [Some("first\nsecond".to_string()), Some("a constant".to_string()), None]);
+}
+
+#[test]
+fn identifiers_follow_unicode_rules() {
+    assert_eq!(output("let größe = 2\nlet 変数 = 3\nlet _x1 = größe + 変数\nprint(_x1)"), "5\n");
+
+    for source in ["let 😀 = 1", "let ١ = 1"] {
+        let err = lexer::scan(source).unwrap_err().to_string();
+
+        assert!(err.starts_with("1:5: unexpected character"), "{err}");
+    }
+}
+
+#[test]
+fn invalid_utf8_is_reported_where_it_is() {
+    assert_eq!(lexer::scan_bytes(b"let a = \"ok\xff\"").unwrap_err().to_string(), "1:12: invalid utf-8 at byte 11");
+    assert_eq!(lexer::scan_bytes(b"let a = 1\nlet \xe2\x82 = 1").unwrap_err().to_string(), "2:5: invalid utf-8 at byte 14");
+}
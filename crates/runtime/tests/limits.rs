@@ -0,0 +1,78 @@
+// runaway recursion and deeply nested code fail with an error instead of
+// overflowing the host's stack, whichever backend runs them and however
+// small the thread they run on
+
+mod common;
+
+use coda_runtime::runtime::interpreter::Backend;
+use common::{error, run, run_on, run_with};
+
+const RECURSION: &str = "fn f(n) { return f(n + 1) } f(0)";
+
+// recursion through every kind of block the tree walker recurses into
+const NESTED_RECURSION: &str = "fn f(n) { if true { while true { for x in [1] { try { return f(n + 1) } finally {} } } } } f(0)";
+
+#[test]
+fn recursion_stops_at_the_call_depth_limit() {
+    let run = run(RECURSION);
+
+    assert_eq!(run.error.as_deref(), Some("1:18: maximum call depth of 512 exceeded"));
+    assert!(run.traceback.unwrap().ends_with("f (<input>:1)\n  [previous frame repeated 510 more times]"));
+
+    let run = run_with(NESTED_RECURSION, &[], |interpreter| interpreter.max_call_depth = 100);
+
+    assert_eq!(run.error.as_deref(), Some("1:62: maximum call depth of 100 exceeded"));
+}
+
+// the test harness runs each test on a thread with the default stack size,
+// which the tree walker can run out of before the call depth limit
+#[test]
+fn recursion_fails_cleanly_on_a_small_stack() {
+    for source in [RECURSION, NESTED_RECURSION, "fn f(n) { return 1 + f(n) + 1 } f(0)"] {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let run = run_on(backend, source, &[], |_| {});
+            let error = run.error.unwrap();
+
+            assert_eq!(run.kind, Some("runtime"), "{backend:?}: {source}");
+            assert!(error.ends_with("stack overflow") || error.ends_with("maximum call depth of 512 exceeded"), "{backend:?}: {error}");
+        }
+    }
+}
+
+#[test]
+fn nesting_fails_cleanly_on_a_small_stack() {
+    let nested = |open: &str, close: &str| format!("print({}1{})", open.repeat(250), close.repeat(250));
+
+    for source in [nested("(", ")"), nested("[", "]"), nested("-", ""), nested("fn() { return ", " }()")] {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let run = run_on(backend, &source, &[], |_| {});
+
+            if let Some(error) = run.error {
+                assert!(error.ends_with("code is nested too deeply") || error.ends_with("stack overflow"), "{backend:?}: {error}");
+            }
+        }
+    }
+}
+
+#[test]
+fn nesting_up_to_the_limit_runs_with_room_to_spare() {
+    let source = format!("print({}1{})", "(".repeat(250), ")".repeat(250));
+
+    assert_eq!(run(&source).output, "1\n");
+}
+
+// a long chain of operators is one level of nesting, not one per operator,
+// though the tree it builds still has a depth limit of its own
+#[test]
+fn long_operator_chains_are_not_nesting() {
+    let chain = |term: &str, operator: &str, terms: usize| vec![term; terms].join(operator);
+
+    assert_eq!(run(&format!("print({})", chain("1", " + ", 1000))).output, "1000\n");
+    assert_eq!(run(&format!("print({})", chain("1", " * ", 1000))).output, "1\n");
+    assert_eq!(run(&format!("print({})", chain("1 == 1", " && ", 500))).output, "true\n");
+
+    let sum = |terms: usize| chain("1", " + ", terms);
+
+    assert_eq!(error(&format!("print({})", sum(5000))), "1:7: code is nested too deeply");
+    assert_eq!(error(&format!("print(({}) + {})", sum(3000), sum(3000))), "1:8: code is nested too deeply");
+}
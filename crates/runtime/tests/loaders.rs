@@ -0,0 +1,98 @@
+// imports ask the module loader before the disk: a chain of loaders is tried
+// in order, and a memory loader is keyed by the path the interpreter asks for
+
+mod common;
+
+use coda_runtime::{
+    env::Env,
+    error::CodaError,
+    frontend::{lexer, parser},
+    runtime::{
+        interpreter::{Backend, Interpreter},
+        loader::{LoaderChain, MemoryLoader, ModuleSource},
+        value::Value,
+    },
+};
+use common::{TempDir, run_with};
+
+// runs `source` on both backends with the loader `loader` builds, giving back
+// what it left in `result` or the error it stopped with
+fn result(source: &str, base_path: &std::path::Path, loader: impl Fn() -> LoaderChain) -> String {
+    let results = [Backend::Tree, Backend::Vm].map(|backend| {
+        let mut interpreter = Interpreter::new(Env::new(), base_path.to_path_buf(), Some(Box::new(loader())));
+
+        interpreter.backend = backend;
+
+        match lexer::scan(source).and_then(parser::parse).and_then(|ast| interpreter.run(ast)) {
+            Ok(()) => interpreter.env.borrow().get("result").map_or("null".to_string(), |value| value.to_string()),
+            Err(err) => err.to_string(),
+        }
+    });
+
+    assert_eq!(results[0], results[1], "the backends disagree on:\n{source}");
+
+    results[1].clone()
+}
+
+fn source(text: &'static str) -> impl Fn(&str) -> Result<Option<ModuleSource>, CodaError> {
+    move |path| Ok((path == "shared").then(|| ModuleSource::Source(text.to_string())))
+}
+
+#[test]
+fn memory_keys_follow_the_base_path() {
+    let import = "import \"./lib/util.coda\"\nprint(x)";
+
+    for (base, key) in [
+        ("", "lib/util.coda"),
+        (".", "lib/util.coda"),
+        ("scripts", "scripts/lib/util.coda"),
+        ("/abs/scripts", "/abs/scripts/lib/util.coda"),
+    ] {
+        let run = run_with(import, &[(key, "import std.io\nexport let x = 1")], |interpreter| interpreter.base_path = base.into());
+
+        assert_eq!(run.error, None, "base path {base:?}");
+        assert_eq!(run.output, "1\n");
+    }
+
+    let run = run_with(import, &[("lib/util.coda", "export let x = 1")], |interpreter| interpreter.base_path = "scripts".into());
+
+    assert!(run.error.unwrap().starts_with("1:1: cannot read module `./lib/util.coda`"));
+}
+
+#[test]
+fn the_first_loader_that_knows_a_path_wins() {
+    let chain = || LoaderChain::new().with(source("export let result = \"first\"")).with(source("export let result = \"second\""));
+
+    assert_eq!(result("import shared", "".as_ref(), chain), "first");
+
+    // a loader that passes leaves the path to the next one
+    let chain = || LoaderChain::new().with(|_: &str| Ok(None)).with(MemoryLoader::new().with("shared", "export let result = 2"));
+
+    assert_eq!(result("import shared", "".as_ref(), chain), "2");
+}
+
+#[test]
+fn a_failing_loader_stops_the_chain() {
+    let chain = || {
+        LoaderChain::new()
+            .with(|path: &str| Err(CodaError::import(format!("no `{path}` for you"))))
+            .with(source("export let result = 1"))
+    };
+
+    assert_eq!(result("import shared", "".as_ref(), chain), "1:1: no `shared` for you");
+}
+
+#[test]
+fn paths_no_loader_knows_fall_back_to_the_disk() {
+    let dir = TempDir::new(&[("disk.coda", "export let result = \"disk\"")]);
+
+    assert_eq!(result("import \"./disk.coda\"", dir.path(), || LoaderChain::new().with(MemoryLoader::new())), "disk");
+}
+
+#[test]
+fn native_modules_export_everything_they_register() {
+    let chain = || LoaderChain::new().with(|path: &str| Ok((path == "host").then(|| ModuleSource::Native(Box::new(|env: &mut Env| env.define_const("answer".to_string(), Value::Int(42)))))));
+
+    assert_eq!(result("import host as h\nlet result = h.answer", "".as_ref(), chain), "42");
+    assert_eq!(result("import { answer } from host\nlet result = answer", "".as_ref(), chain), "42");
+}
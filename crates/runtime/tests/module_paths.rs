@@ -0,0 +1,53 @@
+// bare imports like `mylib.strings` are looked up under each of the
+// interpreter's module roots in turn, as a file or a directory's `mod.coda`
+
+mod common;
+
+use coda_runtime::runtime::interpreter::coda_path;
+use common::{Run, TempDir, run_with};
+
+fn run_with_roots(source: &str, roots: &[&TempDir]) -> Run {
+    run_with(source, &[], |interpreter| interpreter.module_paths = roots.iter().map(|root| root.path().to_path_buf()).collect())
+}
+
+#[test]
+fn dotted_names_map_to_files_and_mod_files() {
+    let root = TempDir::new(&[("mylib/strings.coda", "export let s = \"strings\""), ("pkg/mod.coda", "export let p = \"pkg\"")]);
+
+    assert_eq!(run_with_roots("import mylib.strings\nimport pkg as p\nprint(s, p.p)", &[&root]).output, "strings pkg\n");
+}
+
+#[test]
+fn earlier_roots_win() {
+    let first = TempDir::new(&[("mylib/strings.coda", "export let s = \"first\"")]);
+    let second = TempDir::new(&[("mylib/strings.coda", "export let s = \"second\""), ("other.coda", "export let o = \"other\"")]);
+
+    assert_eq!(run_with_roots("import mylib.strings\nimport other\nprint(s, o)", &[&first, &second]).output, "first other\n");
+}
+
+#[test]
+fn missing_modules_list_the_roots_searched() {
+    let (first, second) = (TempDir::new(&[]), TempDir::new(&[]));
+    let run = run_with_roots("import nope.thing", &[&first, &second]);
+    let error = run.error.unwrap();
+
+    assert!(error.starts_with("1:1: cannot read module `nope.thing`"), "{error}");
+    assert!(error.ends_with(&format!("(searched {}, {})", first.path().display(), second.path().display())), "{error}");
+
+    // paths aren't searched for, so there's nothing to list
+    assert!(!run_with_roots("import \"mylib/strings.coda\"", &[&first]).error.unwrap().contains("searched"));
+}
+
+// the only test here that touches the environment, since tests share it
+#[test]
+fn coda_path_splits_like_path() {
+    let paths = std::env::join_paths(["/one", "", "/two"]).unwrap();
+
+    unsafe { std::env::set_var("CODA_PATH", paths) };
+
+    assert_eq!(coda_path(), [std::path::PathBuf::from("/one"), std::path::PathBuf::from("/two")]);
+
+    unsafe { std::env::remove_var("CODA_PATH") };
+
+    assert!(coda_path().is_empty());
+}
@@ -0,0 +1,88 @@
+// files imported from disk: relative paths resolve against the importing
+// file, there's one copy per file however its path is spelled, and cycles
+// between files are reported with the chain that caused them
+
+mod common;
+
+use common::{Run, TempDir, run_files, run_with};
+
+// runs `source` as `main.coda` in `dir`
+fn run_in(dir: &TempDir, source: &str) -> Run {
+    run_with(source, &[], |interpreter| {
+        interpreter.base_path = dir.path().to_path_buf();
+        interpreter.file = Some(dir.join("main.coda").into());
+    })
+}
+
+#[test]
+fn relative_imports_resolve_against_the_importing_file() {
+    let dir = TempDir::new(&[
+        ("lib/a.coda", "import \"./b.coda\"\nimport \"./sub/c.coda\"\nexport let a = b + c"),
+        ("lib/b.coda", "export let b = 1"),
+        ("lib/sub/c.coda", "import \"../b.coda\" as lib\nexport let c = lib.b + 10"),
+        ("b.coda", "export let b = 100"),
+    ]);
+
+    assert_eq!(run_in(&dir, "import \"./lib/a.coda\"\nimport \"./b.coda\" as top\nprint(a, top.b)").output, "12 100\n");
+
+    // and the same goes for modules a loader hands out
+    let files = [("lib/a.coda", "import \"./b.coda\"\nexport let a = b"), ("lib/b.coda", "export let b = 5")];
+
+    assert_eq!(run_files("import \"./lib/a.coda\"\nprint(a)", &files).output, "5\n");
+}
+
+#[test]
+fn failed_imports_point_at_the_importing_file() {
+    let dir = TempDir::new(&[("lib/bad.coda", "import \"./missing.coda\"")]);
+    let run = run_in(&dir, "import \"./lib/bad.coda\"");
+    let (main, bad) = (dir.join("main.coda"), dir.join("lib/bad.coda"));
+
+    assert!(run.error.unwrap().starts_with(&format!("{bad}:1:1: cannot read module `./missing.coda`")));
+    assert_eq!(run.traceback, Some(format!("traceback (most recent call last):\n  <script> ({main}:1)\n  <script> ({bad}:1)")));
+
+    let run = run_files("import \"./lib/bad.coda\"", &[("lib/bad.coda", "import \"../nope.coda\"")]);
+
+    assert!(run.error.unwrap().starts_with("lib/bad.coda:1:1: cannot read module `../nope.coda`"));
+}
+
+#[test]
+fn every_spelling_of_a_path_shares_one_module() {
+    let dir = TempDir::new(&[
+        ("a.coda", "import std.io\nprint(\"a ran\")\nexport let n = 1"),
+        ("lib/b.coda", "import \"../a.coda\"\nexport let m = n + 1"),
+    ]);
+
+    let run = run_in(&dir, "import \"./a.coda\"\nimport \"./lib/b.coda\"\nimport \"./lib/../a.coda\" as a\nprint(n, m, a.n)");
+
+    assert_eq!(run.output, "a ran\n1 2 1\n");
+}
+
+#[test]
+fn cycles_are_reported_with_their_chain() {
+    let dir = TempDir::new(&[
+        ("x.coda", "import \"./y.coda\"\nexport let x = 1"),
+        ("y.coda", "import \"./lib/../x.coda\"\nexport let y = 1"),
+        ("main.coda", "import \"./main.coda\""),
+    ]);
+
+    let (x, y, main) = (dir.join("x.coda"), dir.join("y.coda"), dir.join("main.coda"));
+
+    let run = run_in(&dir, "import \"./x.coda\"");
+
+    assert_eq!(run.kind, Some("import"));
+    assert_eq!(run.error, Some(format!("{y}:1:1: circular import: {x} -> {y} -> {x}")));
+
+    // the entry script is part of the chain too
+    let run = run_in(&dir, "import \"./main.coda\"");
+
+    assert_eq!(run.error, Some(format!("{main}:1:1: circular import: {main} -> {main}")));
+}
+
+#[test]
+fn missing_files_are_import_errors() {
+    let dir = TempDir::new(&[]);
+    let run = run_in(&dir, "import \"./nope.coda\"");
+
+    assert_eq!(run.kind, Some("import"));
+    assert!(run.error.unwrap().starts_with(&format!("{}:1:1: cannot read module `./nope.coda`", dir.join("main.coda"))));
+}
@@ -0,0 +1,80 @@
+// natives are closures: they can keep state, fail with an error scripts can
+// catch, and call back into script code through the interpreter
+
+mod common;
+
+use coda_runtime::{
+    error::CodaError,
+    runtime::{
+        interpreter::Interpreter,
+        value::{Arity, Value},
+    },
+};
+use common::{Run, run_with};
+use std::{cell::Cell, rc::Rc};
+
+fn define(interpreter: &mut Interpreter, name: &str, arity: Arity, func: impl Fn(&mut Interpreter, Vec<Value>) -> Result<Value, CodaError> + 'static) {
+    interpreter.env.borrow_mut().define(name.to_string(), Value::native(name, arity, func));
+}
+
+// `apply(f, ...args)` calls `f` with the rest of its arguments
+fn with_apply(source: &str) -> Run {
+    run_with(source, &[], |interpreter| {
+        define(interpreter, "apply", Arity::at_least(1), |interpreter, mut args| {
+            let callee = args.remove(0);
+
+            interpreter.call(callee, args)
+        })
+    })
+}
+
+#[test]
+fn natives_keep_state_between_calls() {
+    let run = run_with("tick() tick() print(tick())", &[], |interpreter| {
+        let count = Rc::new(Cell::new(0));
+
+        define(interpreter, "tick", Arity::exactly(0), move |_, _| {
+            count.set(count.get() + 1);
+
+            Ok(Value::Int(count.get()))
+        })
+    });
+
+    assert_eq!(run.output, "3\n");
+}
+
+#[test]
+fn natives_call_back_into_scripts() {
+    let source = r#"
+fn add(a, b = 10) { return a + b }
+let n = 0
+print(apply(add, 1), apply(add, 1, 2), apply(fn() { n += 1 return n }))
+print(apply(apply, add, 5))
+"#;
+
+    assert_eq!(with_apply(source).output, "11 3 1\n15\n");
+}
+
+#[test]
+fn native_errors_are_catchable() {
+    let source = "try { fail() } catch (e) { print(e.kind, e.message) }\nfail()";
+    let run = run_with(source, &[], |interpreter| define(interpreter, "fail", Arity::exactly(0), |_, _| Err(CodaError::native("it failed"))));
+
+    assert_eq!(run.output, "native it failed\n");
+    assert_eq!(run.error.as_deref(), Some("2:1: it failed"));
+}
+
+#[test]
+fn errors_in_callbacks_pass_through_the_native() {
+    let run = with_apply("try { apply(fn() { throw \"inner\" }) } catch (e) { print(e.message) }\napply(fn(x) {})");
+
+    assert_eq!(run.output, "inner\n");
+    assert_eq!(run.error.as_deref(), Some("2:1: `<anonymous>` expects 1 argument but got 0"));
+}
+
+#[test]
+fn native_arity_is_checked_before_the_call() {
+    let run = with_apply("apply()");
+
+    assert_eq!(run.error.as_deref(), Some("1:1: `apply` expects at least 1 argument but got 0"));
+}
@@ -0,0 +1,53 @@
+// number literals in every base, and ints kept apart from floats
+
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn literals_in_every_base() {
+    assert_eq!(output("print(0xff, 0XFF, 0b1010, 0o777, 1_000_000, 0xdead_beef)"), "255 255 10 511 1000000 3735928559\n");
+}
+
+#[test]
+fn exponents_and_fractions_make_floats() {
+    assert_eq!(output("print(1e3, 1.5e-3, 2E2, 1_0.5, 9223372036854775807)"), "1000.0 0.0015 200.0 10.5 9223372036854775807\n");
+}
+
+#[test]
+fn malformed_literals_are_errors() {
+    assert_eq!(error("let x = 0x"), "1:9: expected digits in hex literal");
+    assert_eq!(error("let x = 0b102"), "1:9: invalid digit `2` in binary literal");
+    assert_eq!(error("let x = 1__0"), "1:9: `_` in a number literal must be between digits");
+    assert_eq!(error("let x = 1_"), "1:9: `_` in a number literal must be between digits");
+    assert_eq!(error("let x = 1e"), "1:9: expected digits in exponent");
+    assert_eq!(error("let x = 9223372036854775808"), "1:9: integer literal is too large");
+    assert_eq!(error("let x = 0xffffffffffffffffff"), "1:9: hex literal is too large");
+}
+
+#[test]
+fn ints_stay_ints_until_mixed_with_floats() {
+    assert_eq!(
+        output("print(7 / 2, -7 / 2, 10 / 5, 7.0 / 2, 1 + 0.5, 2 * 1.5, 1.0, 3 == 3.0, 2 < 2.5)"),
+        "3 -4 2 3.5 1.5 3.0 1.0 true true\n"
+    );
+    assert_eq!(output("print(2 ** 62, 2 ** -2, 1 / 0.0)"), "4611686018427387904 0.25 inf\n");
+}
+
+#[test]
+fn int_arithmetic_is_checked() {
+    assert_eq!(error("let x = 9223372036854775807 + 1"), "1:9: integer overflow: 9223372036854775807 + 1");
+    assert_eq!(error("let x = 9223372036854775807 * 2"), "1:9: integer overflow: 9223372036854775807 * 2");
+    assert_eq!(error("let x = -9223372036854775807 - 2"), "1:9: integer overflow: -9223372036854775807 - 2");
+    assert_eq!(error("let x = 2 ** 63"), "1:9: integer overflow: 2 ** 63");
+    assert_eq!(error("let min = -9223372036854775807 - 1\nlet x = min / -1"), "2:9: integer overflow: -9223372036854775808 / -1");
+    assert_eq!(error("let min = -9223372036854775807 - 1\nlet x = -min"), "2:9: integer overflow: -(-9223372036854775808)");
+    assert_eq!(error("let x = 1 % 0"), "1:9: division by zero");
+}
+
+#[test]
+fn unsupported_operands_name_the_operator() {
+    assert_eq!(error("let x = 1 < \"a\""), "1:9: unsupported operation: Int(1) < String(\"a\")");
+    assert_eq!(error("let x = true ** 1"), "1:9: unsupported operation: Bool(true) ** Int(1)");
+    assert_eq!(error("let x = !1 - null"), "1:9: unsupported operation: Bool(false) - Null");
+}
@@ -0,0 +1,41 @@
+// unary, logical and arithmetic operators, which both backends share through
+// `value::unary` and `value::binary`
+
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn unary_operators() {
+    assert_eq!(output("print(-3, -(1.5), !true, !0, !\"\", !null, !!1)"), "-3 -1.5 false true true true true\n");
+}
+
+#[test]
+fn power_binds_tighter_than_minus_and_groups_right() {
+    assert_eq!(output("print(-2 ** 2, 2 ** 3 ** 2, 2 ** -1, 2.0 ** 0.5 > 1.41)"), "-4 512 0.5 true\n");
+}
+
+#[test]
+fn modulo_takes_the_sign_of_the_divisor() {
+    assert_eq!(output("print(7 % 3, -7 % 3, 7 % -3, 7.5 % 2, -7.5 % 2)"), "1 2 -2 1.5 0.5\n");
+}
+
+#[test]
+fn logical_operators_return_the_deciding_operand() {
+    assert_eq!(output("print(null || \"default\", 0 && 1, 1 && 2, false || false)"), "default 0 2 false\n");
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    let source = r#"
+fn loud() { print("evaluated") return true }
+print(false && loud(), true || loud())
+"#;
+
+    assert_eq!(output(source), "false true\n");
+}
+
+#[test]
+fn negating_a_non_number_is_an_error() {
+    assert_eq!(error("-\"x\""), "1:1: unsupported operation: -String(\"x\")");
+}
@@ -0,0 +1,46 @@
+// the parser reports every syntax error in a file, not just the first, and
+// keeps the statements around them
+
+use coda_runtime::frontend::{lexer, parser};
+
+fn parse(source: &str) -> (usize, Vec<String>) {
+    let (statements, errors) = parser::parse_recovering(lexer::scan(source).unwrap());
+
+    (statements.len(), errors.iter().map(ToString::to_string).collect())
+}
+
+#[test]
+fn reports_every_error() {
+    let (statements, errors) = parse("let = 1\nlet ok = 2\nfn (a) {}\nlet also = 3");
+
+    assert_eq!(statements, 2);
+    assert_eq!(errors, ["1:5: expected identifier", "3:4: expected function name"]);
+}
+
+#[test]
+fn errors_inside_blocks_stay_inside_them() {
+    assert_eq!(parse("if true { let x = }\nlet y = 1"), (2, vec!["1:19: unexpected token".to_string()]));
+    assert_eq!(parse("fn f() { let = 1\nlet fine = 2 }\nf()"), (2, vec!["1:14: expected identifier".to_string()]));
+}
+
+#[test]
+fn an_unfinished_expression_stops_at_the_next_statement() {
+    assert_eq!(parse("let a = (1 +\nlet b = 2"), (1, vec!["2:1: unexpected token".to_string()]));
+}
+
+#[test]
+fn parse_returns_the_first_error() {
+    let err = parser::parse(lexer::scan("let = 1\nlet = 2").unwrap()).unwrap_err();
+
+    assert_eq!(err.to_string(), "1:5: expected identifier");
+}
+
+#[test]
+fn input_that_ends_early_is_reported_at_the_end() {
+    assert_eq!(parse("let x ="), (0, vec!["1:8: unexpected token".to_string()]));
+    assert_eq!(parse("print(1 +"), (0, vec!["1:10: unexpected token".to_string()]));
+
+    let (_, errors) = parser::parse_recovering(lexer::scan("let x =").unwrap());
+
+    assert_eq!(errors[0].info().span.map(|span| span.start), Some(7));
+}
@@ -0,0 +1,116 @@
+// variables are bound to frame slots or module globals before anything runs,
+// so scope mistakes are reported up front
+
+mod common;
+
+use common::{error, output, run, run_files};
+
+#[test]
+fn locals_shadow_and_closures_capture() {
+    let source = r#"
+let x = "global"
+fn f() {
+    let x = "local"
+    if true { let x = "inner" print(x) }
+    print(x)
+    return fn() { return x }
+}
+let g = f()
+print(g(), x)
+"#;
+
+    assert_eq!(output(source), "inner\nlocal\nlocal global\n");
+}
+
+#[test]
+fn functions_can_call_ones_declared_later() {
+    let source = r#"
+fn is_even(n) { if n == 0 { return true } return is_odd(n - 1) }
+fn is_odd(n) { if n == 0 { return false } return is_even(n - 1) }
+print(is_even(10), is_odd(7))
+"#;
+
+    assert_eq!(output(source), "true true\n");
+}
+
+#[test]
+fn undefined_variables_fail_before_running() {
+    let run = run("print(\"never printed\")\nprint(missing)");
+
+    assert_eq!(run.output, "");
+    assert_eq!(run.error.as_deref(), Some("2:7: undefined variable `missing`"));
+}
+
+#[test]
+fn duplicate_declarations_are_errors() {
+    assert_eq!(error("let a = 1\nfn a() {}"), "2:1: `a` is already declared");
+    assert_eq!(error("fn f() { let b = 1 let b = 2 }"), "1:20: `b` is already declared in this scope");
+}
+
+#[test]
+fn imports_run_in_source_order() {
+    let files = [("m.coda", "import std.io\nprint(\"loading m\")\nexport let n = 1\nexport fn get() { return n }")];
+    let source = r#"
+print("start")
+import "./m.coda"
+n = 5
+import { get } from "./m.coda"
+import "./m.coda" as m
+print(n, get(), m.n)
+"#;
+
+    let run = run_files(source, &files);
+
+    assert_eq!(run.error, None);
+    assert_eq!(run.output, "start\nloading m\n5 1 1\n");
+}
+
+#[test]
+fn names_are_unbound_until_their_import_runs() {
+    let files = [("m.coda", "export let n = 1")];
+
+    assert_eq!(run_files("print(n)\nimport \"./m.coda\"", &files).error.as_deref(), Some("1:7: undefined variable `n`"));
+    assert_eq!(run_files("import \"./m.coda\" as m\nm = 1", &files).error.as_deref(), Some("2:1: cannot assign to constant `m`"));
+}
+
+#[test]
+fn plain_imports_declare_only_what_the_module_exports() {
+    let files = [("m.coda", "export let n = 1\nlet hidden = 2")];
+    let run = run_files("print(\"start\")\nimport \"./m.coda\"\nprint(n, hidden)", &files);
+
+    assert_eq!(run.output, "");
+    assert_eq!(run.kind, Some("resolve"));
+    assert_eq!(run.error.as_deref(), Some("3:10: undefined variable `hidden`"));
+    assert_eq!(error("import std.math\nprint(tau)"), "2:7: undefined variable `tau`");
+
+    // a module that can't be read fails at its import rather than at the names
+    // it was meant to bring in
+    let run = run_files("print(\"start\")\nimport \"./nope.coda\"\nprint(n)", &files);
+
+    assert_eq!(run.output, "start\n");
+    assert_eq!(run.kind, Some("import"));
+}
+
+#[test]
+fn constants_cannot_be_assigned() {
+    assert_eq!(error("const a = 1\na = 2"), "2:1: cannot assign to constant `a`");
+    assert_eq!(error("const a = 1\na += 1"), "2:1: cannot assign to constant `a`");
+    assert_eq!(error("fn f() { const b = 1 return fn() { b = 2 } }"), "1:36: cannot assign to constant `b`");
+}
+
+#[test]
+fn constants_are_checked_before_running() {
+    let run = run("print(\"never printed\")\nconst a = 1\nfn f() { a = 2 }");
+
+    assert_eq!(run.output, "");
+    assert_eq!(run.kind, Some("resolve"));
+}
+
+#[test]
+fn imported_constants_are_checked_before_running() {
+    let run = run("import std.math\nprint(\"before\")\npi = 3");
+
+    assert_eq!(run.output, "");
+    assert_eq!(run.error.as_deref(), Some("3:1: cannot assign to constant `pi`"));
+    assert_eq!(run.kind, Some("resolve"));
+}
@@ -0,0 +1,33 @@
+// string escapes, raw strings and `${}` interpolation
+
+mod common;
+
+use common::{error, output};
+
+#[test]
+fn escapes() {
+    assert_eq!(output(r#"print("a\tb\n\\ \" \u{e9}\u{1F600} \0 \$")"#), "a\tb\n\\ \" é😀 \0 $\n");
+}
+
+#[test]
+fn raw_strings_keep_backslashes_and_quotes() {
+    assert_eq!(output(r##"print(r"\n ${x}", r#"say "hi""#)"##), "\\n ${x} say \"hi\"\n");
+}
+
+#[test]
+fn interpolation_evaluates_expressions() {
+    let source = r#"
+let n = 2
+print("n=${n} sum=${n + 1} nested=${"in${n}"} ${[1, 2]} \${n}")
+"#;
+
+    assert_eq!(output(source), "n=2 sum=3 nested=in2 [1, 2] ${n}\n");
+}
+
+#[test]
+fn bad_strings_are_errors() {
+    assert_eq!(error(r#"let s = "\q""#), "1:10: unknown escape sequence `\\q`");
+    assert_eq!(error(r#"let s = "\u{110000}""#), "1:10: `\\u{110000}` is not a valid unicode character");
+    assert_eq!(error(r#"let s = "abc"#), "1:9: unterminated string");
+    assert_eq!(error(r#"let s = "${1 + }""#), "1:16: unexpected token");
+}
@@ -0,0 +1,68 @@
+// errors that leave a call carry the stack they came out of, outermost first,
+// with natives and functions from other files shown where they ran
+
+mod common;
+
+use coda_runtime::runtime::value::{Arity, Value};
+use common::{run, run_files, run_with};
+
+#[test]
+fn frames_name_each_call_and_its_line() {
+    let source = "fn a() {\n  return 1 + null\n}\nlet b = fn() {\n  a()\n}\nfn c() { b() }\nc()";
+    let run = run(source);
+
+    assert_eq!(run.error.as_deref(), Some("2:10: unsupported operation: Int(1) + Null"));
+    assert_eq!(
+        run.traceback.as_deref(),
+        Some("traceback (most recent call last):\n  <script> (<input>:8)\n  c (<input>:7)\n  <anonymous> (<input>:5)\n  a (<input>:2)")
+    );
+}
+
+#[test]
+fn errors_outside_any_function_have_no_traceback() {
+    assert_eq!(run("let x = 1\nx()").traceback, None);
+}
+
+#[test]
+fn natives_appear_as_frames() {
+    let run = run("import std.math\nfn f() {\n  sqrt(\"x\")\n}\nf()");
+
+    assert_eq!(
+        run.traceback.as_deref(),
+        Some("traceback (most recent call last):\n  <script> (<input>:5)\n  f (<input>:3)\n  sqrt (native)")
+    );
+
+    // and script code a native calls back into shows up above it
+    let run = run_with("fn f() { apply(fn() { throw 2 }) }\nf()", &[], |interpreter| {
+        let apply = Value::native("apply", Arity::exactly(1), |interpreter, mut args| interpreter.call(args.remove(0), Vec::new()));
+
+        interpreter.env.borrow_mut().define("apply".to_string(), apply);
+    });
+
+    assert_eq!(
+        run.traceback.as_deref(),
+        Some("traceback (most recent call last):\n  <script> (<input>:2)\n  f (<input>:1)\n  apply (native)\n  <anonymous> (<input>:1)")
+    );
+}
+
+#[test]
+fn imported_functions_report_their_own_file() {
+    let run = run_files("import \"./lib.coda\" as lib\nlib.boom()", &[("lib.coda", "export fn boom() {\n  throw \"boom\"\n}")]);
+
+    assert_eq!(run.error.as_deref(), Some("lib.coda:2:3: boom"));
+    assert_eq!(run.traceback.as_deref(), Some("traceback (most recent call last):\n  <script> (<input>:2)\n  boom (lib.coda:2)"));
+}
+
+#[test]
+fn the_entry_file_names_its_frames() {
+    let run = run_with("fn f() {\n  [][1]\n}\nf()", &[], |interpreter| interpreter.file = Some("main.coda".into()));
+
+    assert_eq!(run.traceback.as_deref(), Some("traceback (most recent call last):\n  <script> (main.coda:4)\n  f (main.coda:2)"));
+}
+
+#[test]
+fn caught_errors_expose_the_trace() {
+    let output = run("fn f() {\n  [][1]\n}\ntry { f() } catch (e) { print(e.trace) }").output;
+
+    assert_eq!(output, "[{function: <script>, file: null, line: 4}, {function: f, file: null, line: 2}]\n");
+}
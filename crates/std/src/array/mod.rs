@@ -0,0 +1,79 @@
+use coda_runtime::{
+    env::Env,
+    error::CodaError,
+    runtime::value::{self, Arity, BinaryOp, Value},
+};
+use std::cmp::Ordering;
+
+pub fn register(env: &mut Env) {
+    env.define(
+        "map".to_string(),
+        Value::native("map", Arity::exactly(2), |interpreter, args| {
+            let items = items("map", &args[0])?;
+            let mapped = items.into_iter().map(|item| interpreter.call(args[1].clone(), vec![item])).collect::<Result<_, _>>()?;
+
+            Ok(Value::array(mapped))
+        }),
+    );
+
+    env.define(
+        "filter".to_string(),
+        Value::native("filter", Arity::exactly(2), |interpreter, args| {
+            let mut kept = Vec::new();
+
+            for item in items("filter", &args[0])? {
+                if interpreter.call(args[1].clone(), vec![item.clone()])?.as_bool() {
+                    kept.push(item);
+                }
+            }
+
+            Ok(Value::array(kept))
+        }),
+    );
+
+    // a sorted copy, ordered by the key `f` gives each item. items with equal
+    // keys keep their order
+    env.define(
+        "sort_by".to_string(),
+        Value::native("sort_by", Arity::exactly(2), |interpreter, args| {
+            let items = items("sort_by", &args[0])?;
+            let keys = items.iter().map(|item| interpreter.call(args[1].clone(), vec![item.clone()])).collect::<Result<Vec<_>, _>>()?;
+            let mut order: Vec<usize> = (0..items.len()).collect();
+            let mut error = None;
+
+            order.sort_by(|&a, &b| match compare(&keys[a], &keys[b]) {
+                Ok(ordering) => ordering,
+
+                Err(err) => {
+                    error.get_or_insert(err);
+
+                    Ordering::Equal
+                }
+            });
+
+            match error {
+                Some(err) => Err(err),
+                None => Ok(Value::array(order.into_iter().map(|i| items[i].clone()).collect())),
+            }
+        }),
+    );
+}
+
+// a copy, so callbacks are free to change the array while it's walked
+fn items(name: &str, value: &Value) -> Result<Vec<Value>, CodaError> {
+    match value {
+        Value::Array(items) => Ok(items.borrow().clone()),
+        other => Err(CodaError::native(format!("`{name}` expects an array, got {other:?}"))),
+    }
+}
+
+// ordered the way `<` orders them, so keys that `<` rejects are an error
+fn compare(a: &Value, b: &Value) -> Result<Ordering, CodaError> {
+    if value::binary(a, BinaryOp::Less, b)?.as_bool() {
+        Ok(Ordering::Less)
+    } else if value::binary(b, BinaryOp::Less, a)?.as_bool() {
+        Ok(Ordering::Greater)
+    } else {
+        Ok(Ordering::Equal)
+    }
+}
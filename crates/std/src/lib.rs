@@ -1,5 +1,6 @@
-use coda_runtime::env::Env;
+use coda_runtime::{env::Env, error::CodaError, runtime::loader::ModuleSource};
 
+pub mod array;
 pub mod io;
 pub mod math;
 
@@ -9,33 +10,19 @@ pub fn get_module(path: &str) -> Option<StdRegisterFn> {
     match path {
         "std.math" => Some(math::register),
         "std.io" => Some(io::register),
+        "std.array" => Some(array::register),
         _ => None,
     }
 }
 
-pub fn std_loader(
-    path: &str,
-    env: &mut Env,
-) -> Result<bool, Box<dyn std::error::Error>> {
-    if path.starts_with("std.") {
-        match path {
-            "std.math" => {
-                math::register(env);
-
-                return Ok(true);
-            }
-
-            "std.io" => {
-                io::register(env);
-
-                return Ok(true);
-            }
-
-            _ => {
-                return Err(format!("unknown std module `{path}`").into());
-            }
-        }
+// answers every `std.*` import, leaving other paths to the next loader
+pub fn std_loader(path: &str) -> Result<Option<ModuleSource>, CodaError> {
+    if !path.starts_with("std.") {
+        return Ok(None);
     }
 
-    Ok(false)
+    match get_module(path) {
+        Some(register) => Ok(Some(ModuleSource::Native(Box::new(register)))),
+        None => Err(CodaError::import(format!("unknown std module `{path}`"))),
+    }
 }
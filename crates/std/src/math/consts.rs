@@ -1,11 +1,19 @@
-use coda_runtime::{env::Env, runtime::value::Value};
+use coda_runtime::{
+    env::Env,
+    error::CodaError,
+    runtime::value::{Arity, Value},
+};
 
 pub fn register(env: &mut Env) {
-    env.define("pi".to_string(), Value::Number(std::f64::consts::PI));
-    env.define("e".to_string(), Value::Number(std::f64::consts::E));
+    env.define_const("pi".to_string(), Value::Number(std::f64::consts::PI));
+    env.define_const("e".to_string(), Value::Number(std::f64::consts::E));
 
     env.define(
         "sqrt".to_string(),
-        Value::NativeFunction(|args| if let Some(Value::Number(n)) = args.get(0) { Value::Number(n.sqrt()) } else { Value::Null }),
+        Value::native("sqrt", Arity::exactly(1), |_, args| match &args[0] {
+            Value::Int(n) => Ok(Value::Number((*n as f64).sqrt())),
+            Value::Number(n) => Ok(Value::Number(n.sqrt())),
+            other => Err(CodaError::native(format!("`sqrt` expects a number, got {other:?}"))),
+        }),
     );
 }
@@ -0,0 +1,52 @@
+// the higher-order array functions call back into the script, so each one is
+// checked on both backends
+
+use coda_runtime::{
+    env::Env,
+    frontend::{lexer, parser},
+    runtime::interpreter::{Backend, Interpreter},
+};
+use coda_std::std_loader;
+
+// runs `source` after `import std.array`, giving back what it left in `result`
+// or the error it stopped with
+fn result(source: &str) -> String {
+    let results = [Backend::Tree, Backend::Vm].map(|backend| {
+        let mut interpreter = Interpreter::new(Env::new(), std::path::PathBuf::new(), Some(Box::new(std_loader)));
+
+        interpreter.backend = backend;
+
+        let source = format!("import std.array\n{source}");
+        let ran = lexer::scan(&source).and_then(parser::parse).and_then(|ast| interpreter.run(ast));
+
+        match ran {
+            Ok(()) => interpreter.env.borrow().get("result").map_or("null".to_string(), |value| value.to_string()),
+            Err(err) => err.to_string(),
+        }
+    });
+
+    assert_eq!(results[0], results[1], "the backends disagree on:\n{source}");
+
+    results[1].clone()
+}
+
+#[test]
+fn map_and_filter() {
+    assert_eq!(result("let result = map([1, 2, 3], fn(x) { return x * 2 })"), "[2, 4, 6]");
+    assert_eq!(result("let result = filter([1, 2, 3, 4], fn(x) { return x % 2 == 0 })"), "[2, 4]");
+    assert_eq!(result("let n = 0\nmap([1, 2], fn(x) { n += x })\nlet result = n"), "3");
+}
+
+#[test]
+fn sort_by_is_stable() {
+    let source = r#"let result = sort_by([{ k: 2, v: "a" }, { k: 1, v: "b" }, { k: 2, v: "c" }], fn(x) { return x.k })"#;
+
+    assert_eq!(result(source), "[{k: 1, v: b}, {k: 2, v: a}, {k: 2, v: c}]");
+}
+
+#[test]
+fn bad_arguments_and_callback_errors_are_reported() {
+    assert_eq!(result("let result = map(1, fn(x) { return x })"), "2:14: `map` expects an array, got Int(1)");
+    assert!(result("let result = sort_by([1, \"a\"], fn(x) { return x })").starts_with("2:14: unsupported operation"));
+    assert_eq!(result("let result = 0\ntry { filter([1], fn(x) { throw \"no\" }) } catch (e) { result = e.message }"), "no");
+}